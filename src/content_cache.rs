@@ -0,0 +1,230 @@
+use crate::errors::BuildError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Name of the cache file, kept in the output directory next to the pages it
+/// describes.
+pub const CACHE_FILE: &str = ".crustdown-cache.json";
+
+/// Hashes everything an output is built from. Each part is length-prefixed
+/// so that moving bytes from one part to the next changes the result.
+#[derive(Default)]
+pub struct Fingerprint(blake3::Hasher);
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<T: AsRef<[u8]>>(&mut self, part: T) -> &mut Self {
+        let part = part.as_ref();
+        self.0.update(&(part.len() as u64).to_le_bytes());
+        self.0.update(part);
+        self
+    }
+
+    /// Adds a value through its JSON representation
+    pub fn add_json<T: Serialize>(&mut self, value: &T) -> &mut Self {
+        self.add(serde_json::to_vec(value).unwrap_or_default())
+    }
+
+    pub fn finish(&self) -> String {
+        self.0.finalize().to_hex().to_string()
+    }
+}
+
+/// What the previous build wrote: the fingerprint of the inputs of every
+/// output file and what it was built from, keyed by its path in the output
+/// directory. An output whose fingerprint didn't change doesn't need to be
+/// rendered or written again.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildCache {
+    /// Version of crustdown that wrote the cache, a different version
+    /// rebuilds everything as its templates or rendering may have changed
+    version: String,
+    outputs: BTreeMap<String, CachedOutput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedOutput {
+    fingerprint: String,
+    /// The post, page or file the output is built from, as named in errors
+    source: String,
+}
+
+impl BuildCache {
+    pub fn new() -> Self {
+        BuildCache {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    /// The cache of the last build in `output_dir`, if there is a usable one
+    pub fn load(output_dir: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(output_dir.join(CACHE_FILE)).ok()?;
+        let cache: BuildCache = serde_json::from_str(&content).ok()?;
+        (cache.version == env!("CARGO_PKG_VERSION")).then_some(cache)
+    }
+
+    pub fn save(&self, output_dir: &Path) -> Result<(), BuildError> {
+        let content = serde_json::to_string_pretty(self).unwrap_or_default();
+        std::fs::write(output_dir.join(CACHE_FILE), content).map_err(|e| BuildError::Write {
+            path: CACHE_FILE.to_string(),
+            message: e.to_string(),
+        })
+    }
+
+    pub fn insert(&mut self, path: &str, fingerprint: String, source: &str) {
+        self.outputs.insert(
+            path.to_string(),
+            CachedOutput {
+                fingerprint,
+                source: source.to_string(),
+            },
+        );
+    }
+
+    /// Whether `path` was built from the same inputs and is still in
+    /// `output_dir`
+    pub fn is_fresh(&self, output_dir: &Path, path: &str, fingerprint: &str) -> bool {
+        self.outputs
+            .get(path)
+            .is_some_and(|previous| previous.fingerprint == fingerprint)
+            && output_dir.join(path).is_file()
+    }
+
+    /// Carries over the outputs `previous` built from the sources in `failed`,
+    /// so that a post that fails to build keeps its last good page instead
+    /// of having it removed as stale. Files copied into the same directory
+    /// as these outputs are kept too, as long as their source is still there.
+    pub fn keep_failed(&mut self, previous: &BuildCache, failed: &BTreeSet<String>) {
+        let kept = previous
+            .outputs
+            .iter()
+            .filter(|(path, output)| {
+                !self.outputs.contains_key(*path)
+                    && (failed.contains(&output.source) || failed.contains(*path))
+            })
+            .collect::<Vec<_>>();
+        let kept_dirs = kept
+            .iter()
+            .map(|(path, _)| Path::new(path.as_str()).parent())
+            .collect::<BTreeSet<_>>();
+        let copied = previous.outputs.iter().filter(|(path, output)| {
+            !self.outputs.contains_key(*path)
+                && kept_dirs.contains(&Path::new(path.as_str()).parent())
+                && Path::new(&output.source).is_file()
+        });
+        let carried = kept
+            .iter()
+            .copied()
+            .chain(copied)
+            .map(|(path, output)| (path.clone(), output.clone()))
+            .collect::<Vec<_>>();
+        self.outputs.extend(carried);
+    }
+
+    /// Outputs of this cache that are not part of `current` anymore, like the
+    /// pages of deleted posts or tags nobody uses.
+    pub fn stale_outputs<'a>(&'a self, current: &BuildCache) -> Vec<&'a str> {
+        self.outputs
+            .keys()
+            .filter(|path| !current.outputs.contains_key(*path))
+            .map(|path| path.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = Fingerprint::new().add("ab").add("c").finish();
+        assert_eq!(fingerprint, Fingerprint::new().add("ab").add("c").finish());
+        assert_ne!(fingerprint, Fingerprint::new().add("a").add("bc").finish());
+        assert_ne!(fingerprint, Fingerprint::new().add("ab").add("d").finish());
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(BuildCache::load(temp_dir.path()), None);
+
+        let mut cache = BuildCache::new();
+        cache.insert("index.html", "1234".to_string(), "the index");
+        cache.save(temp_dir.path()).unwrap();
+        assert_eq!(BuildCache::load(temp_dir.path()), Some(cache));
+
+        std::fs::write(temp_dir.path().join(CACHE_FILE), "not json").unwrap();
+        assert_eq!(BuildCache::load(temp_dir.path()), None);
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = BuildCache::new();
+        cache.insert("index.html", "1234".to_string(), "the index");
+
+        assert!(!cache.is_fresh(temp_dir.path(), "index.html", "1234"));
+        std::fs::write(temp_dir.path().join("index.html"), "index").unwrap();
+        assert!(cache.is_fresh(temp_dir.path(), "index.html", "1234"));
+        assert!(!cache.is_fresh(temp_dir.path(), "index.html", "5678"));
+        assert!(!cache.is_fresh(temp_dir.path(), "feed.xml", "1234"));
+    }
+
+    #[test]
+    fn test_stale_outputs() {
+        let mut previous = BuildCache::new();
+        previous.insert("index.html", "1".to_string(), "the index");
+        previous.insert(
+            "posts/deleted/index.html",
+            "2".to_string(),
+            "_posts/deleted.md",
+        );
+        let mut current = BuildCache::new();
+        current.insert("index.html", "3".to_string(), "the index");
+
+        assert_eq!(
+            previous.stale_outputs(&current),
+            vec!["posts/deleted/index.html"]
+        );
+    }
+
+    #[test]
+    fn test_keep_failed() {
+        let temp_dir = TempDir::new().unwrap();
+        let photo = temp_dir.path().join("photo.jpg");
+        std::fs::write(&photo, "photo").unwrap();
+        let photo = photo.display().to_string();
+        let mut previous = BuildCache::new();
+        previous.insert("index.html", "1".to_string(), "the index");
+        previous.insert(
+            "posts/broken/index.html",
+            "2".to_string(),
+            "_posts/broken.md",
+        );
+        previous.insert("posts/broken/photo.jpg", "3".to_string(), &photo);
+        previous.insert("posts/broken/gone.jpg", "4".to_string(), "_posts/gone.jpg");
+        previous.insert(
+            "posts/deleted/index.html",
+            "5".to_string(),
+            "_posts/deleted.md",
+        );
+        let mut current = BuildCache::new();
+        current.insert("index.html", "6".to_string(), "the index");
+
+        current.keep_failed(&previous, &BTreeSet::from(["_posts/broken.md".to_string()]));
+        assert_eq!(current.outputs["posts/broken/index.html"].fingerprint, "2");
+        assert_eq!(current.outputs["posts/broken/photo.jpg"].fingerprint, "3");
+        assert_eq!(current.outputs["index.html"].fingerprint, "6");
+        assert_eq!(
+            previous.stale_outputs(&current),
+            vec!["posts/broken/gone.jpg", "posts/deleted/index.html"]
+        );
+    }
+}
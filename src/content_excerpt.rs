@@ -0,0 +1,94 @@
+use crate::content_headings::html_text;
+use crate::entities::Excerpt;
+
+/// Marks the end of the excerpt of a post, on a line of its own
+pub const EXCERPT_SEPARATOR: &str = "<!--more-->";
+
+/// The separator as rendered, kept as is when raw HTML is allowed and
+/// escaped otherwise
+fn find_separator(html: &str) -> Option<(usize, usize)> {
+    let escaped = EXCERPT_SEPARATOR.replace('<', "&lt;").replace('>', "&gt;");
+    [EXCERPT_SEPARATOR, escaped.as_str()]
+        .into_iter()
+        .filter_map(|separator| {
+            html.match_indices(separator)
+                .find(|(index, _)| *index == 0 || html[..*index].ends_with('\n'))
+                .map(|(index, separator)| (index, index + separator.len()))
+        })
+        .min()
+}
+
+/// The first `<p>` of `html` with its content
+fn first_paragraph(html: &str) -> &str {
+    let Some(start) = html
+        .match_indices("<p>")
+        .map(|(index, _)| index)
+        .find(|index| *index == 0 || html[..*index].ends_with(['\n', '>']))
+    else {
+        return "";
+    };
+    match html[start..].find("</p>") {
+        Some(end) => &html[start..start + end + "</p>".len()],
+        None => "",
+    }
+}
+
+/// Splits the excerpt off the HTML of a post: everything above the
+/// `<!--more-->` separator, which is removed from the content, or else its
+/// first paragraph.
+pub fn split_excerpt(html: &str) -> (String, Excerpt) {
+    let (content, excerpt_html) = match find_separator(html) {
+        Some((start, end)) => {
+            let after = html[end..].strip_prefix('\n').unwrap_or(&html[end..]);
+            let content = format!("{}{}", &html[..start], after);
+            (content, html[..start].trim_end().to_string())
+        }
+        None => (html.to_string(), first_paragraph(html).to_string()),
+    };
+    let text = html_text(&excerpt_html)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (
+        content,
+        Excerpt {
+            html: excerpt_html,
+            text,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_split_excerpt_first_paragraph() {
+        let html = "<h2 id=\"intro\">Intro</h2>\n<p>First <em>paragraph</em>\nof &amp; the post.</p>\n<p>Second.</p>";
+        let (content, excerpt) = split_excerpt(html);
+        assert_eq!(content, html);
+        assert_eq!(
+            excerpt.html,
+            "<p>First <em>paragraph</em>\nof &amp; the post.</p>"
+        );
+        assert_eq!(excerpt.text, "First paragraph of & the post.");
+
+        let (_, excerpt) = split_excerpt("<pre><code>only code\n</code></pre>");
+        assert_eq!(excerpt.html, "");
+        assert_eq!(excerpt.text, "");
+    }
+
+    #[test]
+    fn test_split_excerpt_separator() {
+        let (content, excerpt) = split_excerpt("<p>One</p>\n<p>Two</p>\n<!--more-->\n<p>Three</p>");
+        assert_eq!(content, "<p>One</p>\n<p>Two</p>\n<p>Three</p>");
+        assert_eq!(excerpt.html, "<p>One</p>\n<p>Two</p>");
+        assert_eq!(excerpt.text, "One Two");
+
+        let (content, excerpt) =
+            split_excerpt("<p>One</p>\n&lt;!--more--&gt;\n<p>Two &lt;!--more--&gt;</p>");
+        assert_eq!(content, "<p>One</p>\n<p>Two &lt;!--more--&gt;</p>");
+        assert_eq!(excerpt.html, "<p>One</p>");
+    }
+}
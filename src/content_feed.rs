@@ -0,0 +1,208 @@
+use crate::entities::{Feed, FeedContent, FeedItem, PostOutput, SiteConfig};
+use crate::errors::BuildError;
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub const RSS_PATH: &str = "feed.xml";
+pub const ATOM_PATH: &str = "atom.xml";
+
+fn to_datetime(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+fn absolute_url(base_url: &str, route: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        route.trim_start_matches('/')
+    )
+}
+
+/// Whether the site has feeds: it has a `base_url` and they aren't turned off
+pub fn feeds_enabled(site_config: &SiteConfig) -> bool {
+    site_config.base_url.is_some()
+        && site_config
+            .feed
+            .as_ref()
+            .is_none_or(|feed_config| feed_config.enabled)
+}
+
+/// Builds the data shared by the RSS and Atom feeds out of the newest posts.
+/// Returns `None` when feeds are disabled or the site has no `base_url` to
+/// build absolute links from.
+pub fn build_feed(
+    posts: &[PostOutput],
+    site_config: &SiteConfig,
+) -> Result<Option<Feed>, BuildError> {
+    let feed_config = site_config.feed.clone().unwrap_or_default();
+    if !feed_config.enabled {
+        return Ok(None);
+    }
+
+    let base_url = match (&site_config.base_url, &site_config.feed) {
+        (Some(base_url), _) => base_url,
+        (None, Some(_)) => {
+            return Err(BuildError::Config {
+                message: "base_url is required to generate feeds".to_string(),
+            });
+        }
+        (None, None) => return Ok(None),
+    };
+
+    let mut posts = posts.iter().collect::<Vec<_>>();
+    posts.sort_by(|a, b| {
+        b.metadata
+            .date
+            .cmp(&a.metadata.date)
+            .then_with(|| a.route.cmp(&b.route))
+    });
+    posts.truncate(feed_config.limit);
+
+    let updated = to_datetime(
+        posts
+            .first()
+            .map_or(NaiveDate::default(), |post| post.metadata.date),
+    );
+
+    let items = posts
+        .iter()
+        .map(|post| {
+            let published = to_datetime(post.metadata.date);
+            FeedItem {
+                title: post.metadata.title.clone(),
+                link: absolute_url(base_url, &post.url),
+                summary: post.metadata.description.clone(),
+                content: post.content.clone(),
+                published: published.to_rfc3339(),
+                published_rfc2822: published.to_rfc2822(),
+                tags: post.metadata.tags.clone(),
+                stats: post.stats.clone(),
+            }
+        })
+        .collect();
+
+    Ok(Some(Feed {
+        site_url: absolute_url(base_url, ""),
+        rss_url: absolute_url(base_url, RSS_PATH),
+        atom_url: absolute_url(base_url, ATOM_PATH),
+        updated: updated.to_rfc3339(),
+        updated_rfc2822: updated.to_rfc2822(),
+        full_content: feed_config.content == FeedContent::Full,
+        items,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content_router::route_url;
+    use crate::entities::{FeedConfig, Metadata};
+    use pretty_assertions::assert_eq;
+
+    fn post(route: &str, date: &str) -> PostOutput {
+        PostOutput {
+            route: route.to_string(),
+            url: route_url(route),
+            source_path: format!("{}.md", route),
+            metadata: Metadata {
+                title: route.to_uppercase(),
+                description: format!("About {}", route),
+                date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+                tags: vec!["rust".to_string()],
+                ..Default::default()
+            },
+            content: format!("<p>{}</p>", route),
+            ..Default::default()
+        }
+    }
+
+    fn site_config(base_url: Option<&str>, feed: Option<FeedConfig>) -> SiteConfig {
+        SiteConfig {
+            base_url: base_url.map(|base_url| base_url.to_string()),
+            feed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_feed() {
+        let posts = vec![
+            post("posts/old/index.html", "2021-01-01"),
+            post("posts/new/index.html", "2023-05-04"),
+        ];
+        let feed = build_feed(&posts, &site_config(Some("https://example.com/"), None))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(feed.site_url, "https://example.com/");
+        assert_eq!(feed.rss_url, "https://example.com/feed.xml");
+        assert_eq!(feed.atom_url, "https://example.com/atom.xml");
+        assert_eq!(feed.updated, "2023-05-04T00:00:00+00:00");
+        assert_eq!(feed.updated_rfc2822, "Thu, 4 May 2023 00:00:00 +0000");
+        assert!(!feed.full_content);
+        assert_eq!(feed.items.len(), 2);
+        assert_eq!(feed.items[0].link, "https://example.com/posts/new/");
+        assert_eq!(feed.items[0].summary, "About posts/new/index.html");
+        assert_eq!(feed.items[1].published, "2021-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_build_feed_limit() {
+        let posts = vec![
+            post("a", "2021-01-01"),
+            post("b", "2022-01-01"),
+            post("c", "2023-01-01"),
+        ];
+        let feed_config = FeedConfig {
+            limit: 2,
+            content: FeedContent::Full,
+            ..Default::default()
+        };
+        let feed = build_feed(
+            &posts,
+            &site_config(Some("https://example.com"), Some(feed_config)),
+        )
+        .unwrap()
+        .unwrap();
+
+        let links = feed
+            .items
+            .iter()
+            .map(|item| item.link.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec!["https://example.com/c", "https://example.com/b"]
+        );
+        assert!(feed.full_content);
+    }
+
+    #[test]
+    fn test_build_feed_without_base_url() {
+        let posts = vec![post("a", "2021-01-01")];
+        assert!(
+            build_feed(&posts, &site_config(None, None))
+                .unwrap()
+                .is_none()
+        );
+
+        let result = build_feed(&posts, &site_config(None, Some(FeedConfig::default())));
+        assert_eq!(
+            result.err().unwrap().message(),
+            "base_url is required to generate feeds"
+        );
+    }
+
+    #[test]
+    fn test_build_feed_disabled() {
+        let feed_config = FeedConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let feed = build_feed(
+            &[],
+            &site_config(Some("https://example.com"), Some(feed_config)),
+        )
+        .unwrap();
+        assert!(feed.is_none());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::entities::Metadata;
+use chrono::NaiveDate;
+
+pub struct PublishOptions {
+    /// Build everything: drafts, future and expired posts
+    pub drafts: bool,
+    /// Build posts dated after `today`
+    pub future: bool,
+    pub today: NaiveDate,
+}
+
+/// Whether a post makes it into the site. Drafts, posts dated in the future
+/// and posts past their `expires` date are left out unless asked otherwise.
+pub fn is_published(metadata: &Metadata, options: &PublishOptions) -> bool {
+    if options.drafts {
+        return true;
+    }
+    if metadata.draft {
+        return false;
+    }
+    if metadata.date > options.today && !options.future {
+        return false;
+    }
+    metadata
+        .expires
+        .is_none_or(|expires| expires > options.today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn metadata(post_date: &str, draft: bool, expires: Option<&str>) -> Metadata {
+        Metadata {
+            date: date(post_date),
+            draft,
+            expires: expires.map(date),
+            ..Default::default()
+        }
+    }
+
+    fn options(drafts: bool, future: bool) -> PublishOptions {
+        PublishOptions {
+            drafts,
+            future,
+            today: date("2025-05-04"),
+        }
+    }
+
+    #[test]
+    fn test_is_published() {
+        let default = options(false, false);
+        assert!(is_published(&metadata("2025-05-04", false, None), &default));
+        assert!(is_published(
+            &metadata("2025-01-01", false, Some("2025-05-05")),
+            &default
+        ));
+    }
+
+    #[test]
+    fn test_is_published_drafts() {
+        let post = metadata("2025-01-01", true, None);
+        assert!(!is_published(&post, &options(false, false)));
+        assert!(!is_published(&post, &options(false, true)));
+        assert!(is_published(&post, &options(true, false)));
+    }
+
+    #[test]
+    fn test_is_published_future() {
+        let post = metadata("2025-05-05", false, None);
+        assert!(!is_published(&post, &options(false, false)));
+        assert!(is_published(&post, &options(false, true)));
+        assert!(is_published(&post, &options(true, false)));
+    }
+
+    #[test]
+    fn test_is_published_expired() {
+        let post = metadata("2025-01-01", false, Some("2025-05-04"));
+        assert!(!is_published(&post, &options(false, false)));
+        assert!(!is_published(&post, &options(false, true)));
+        assert!(is_published(&post, &options(true, false)));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::content_router::slugify;
+use crate::entities::TocEntry;
+use std::collections::HashSet;
+
+/// Text of an HTML fragment, without its tags and with the entities written
+/// by the markdown renderer decoded.
+pub fn html_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Slug of `text` not in `used` yet, numbered like `intro-1` when it is
+fn unique_id(text: &str, used: &mut HashSet<String>) -> String {
+    let slug = match slugify(text) {
+        slug if slug.is_empty() => "section".to_string(),
+        slug => slug,
+    };
+    let mut id = slug.clone();
+    let mut count = 0;
+    while used.contains(&id) {
+        count += 1;
+        id = format!("{}-{}", slug, count);
+    }
+    used.insert(id.clone());
+    id
+}
+
+/// Gives every heading rendered from markdown an `id` made from its text,
+/// adding a `#` link to it when `anchors` is set. Returns the new HTML with
+/// the headings in order, as entries without children.
+///
+/// Headings with attributes, like the ones written as raw HTML, are left
+/// alone.
+pub fn add_heading_ids(html: &str, anchors: bool) -> (String, Vec<TocEntry>) {
+    let mut output = String::with_capacity(html.len());
+    let mut headings = Vec::new();
+    let mut used = HashSet::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<h") {
+        let level = rest[start + 2..]
+            .chars()
+            .next()
+            .and_then(|c| c.to_digit(10))
+            .filter(|level| (1..=6).contains(level));
+        let opening_end = start + 4;
+        let closing = level.map(|level| format!("</h{}>", level));
+        let (Some(level), Some(closing)) = (level, closing) else {
+            output.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+        let Some(end) = rest
+            .get(opening_end - 1..opening_end)
+            .filter(|c| *c == ">")
+            .and_then(|_| rest[opening_end..].find(&closing))
+            .map(|i| opening_end + i)
+        else {
+            output.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        };
+
+        let content = &rest[opening_end..end];
+        let text = html_text(content);
+        let id = unique_id(&text, &mut used);
+        output.push_str(&rest[..start]);
+        output.push_str(&format!("<h{} id=\"{}\">{}", level, id, content));
+        if anchors {
+            output.push_str(&format!(
+                " <a class=\"heading-anchor\" href=\"#{}\" aria-label=\"Link to this section\">#</a>",
+                id
+            ));
+        }
+        output.push_str(&closing);
+        headings.push(TocEntry {
+            level: level as u8,
+            text,
+            id,
+            children: Vec::new(),
+        });
+        rest = &rest[end + closing.len()..];
+    }
+    output.push_str(rest);
+    (output, headings)
+}
+
+fn insert_entry(entries: &mut Vec<TocEntry>, heading: TocEntry) {
+    match entries.last_mut() {
+        Some(last) if last.level < heading.level => insert_entry(&mut last.children, heading),
+        _ => entries.push(heading),
+    }
+}
+
+/// Nests the headings of a post, every heading becoming a child of the
+/// closest heading above it with a lower level.
+pub fn build_toc(headings: Vec<TocEntry>) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    for heading in headings {
+        insert_entry(&mut toc, heading);
+    }
+    toc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn entry(level: u8, text: &str, id: &str, children: Vec<TocEntry>) -> TocEntry {
+        TocEntry {
+            level,
+            text: text.to_string(),
+            id: id.to_string(),
+            children,
+        }
+    }
+
+    #[test]
+    fn test_add_heading_ids() {
+        let html = "<h1>Intro</h1>\n<p>Text</p>\n<h2>Tom &amp; <em>Jerry</em></h2>\n\
+                    <h2>Intro</h2>\n<h3 class=\"raw\">Raw</h3>\n<hr />\n<h2>¿?</h2>";
+        let (html, headings) = add_heading_ids(html, false);
+        assert_eq!(
+            html,
+            "<h1 id=\"intro\">Intro</h1>\n<p>Text</p>\n<h2 id=\"tom-jerry\">Tom &amp; <em>Jerry</em></h2>\n\
+             <h2 id=\"intro-1\">Intro</h2>\n<h3 class=\"raw\">Raw</h3>\n<hr />\n<h2 id=\"section\">¿?</h2>"
+        );
+        assert_eq!(
+            headings
+                .iter()
+                .map(|heading| (heading.level, heading.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(1, "Intro"), (2, "Tom & Jerry"), (2, "Intro"), (2, "¿?")]
+        );
+    }
+
+    #[test]
+    fn test_add_heading_anchors() {
+        let (html, _) = add_heading_ids("<h2>Setup</h2>", true);
+        assert_eq!(
+            html,
+            "<h2 id=\"setup\">Setup <a class=\"heading-anchor\" href=\"#setup\" aria-label=\"Link to this section\">#</a></h2>"
+        );
+    }
+
+    #[test]
+    fn test_build_toc() {
+        let headings = vec![
+            entry(2, "A", "a", vec![]),
+            entry(3, "A.1", "a-1", vec![]),
+            entry(4, "A.1.1", "a-1-1", vec![]),
+            entry(3, "A.2", "a-2", vec![]),
+            entry(2, "B", "b", vec![]),
+            entry(1, "C", "c", vec![]),
+        ];
+        assert_eq!(
+            build_toc(headings),
+            vec![
+                entry(
+                    2,
+                    "A",
+                    "a",
+                    vec![
+                        entry(3, "A.1", "a-1", vec![entry(4, "A.1.1", "a-1-1", vec![])]),
+                        entry(3, "A.2", "a-2", vec![]),
+                    ]
+                ),
+                entry(2, "B", "b", vec![]),
+                entry(1, "C", "c", vec![]),
+            ]
+        );
+    }
+}
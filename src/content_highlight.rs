@@ -0,0 +1,435 @@
+use crate::entities::{HighlightConfig, HighlightStyle};
+use crate::errors::BuildError;
+use markdown::mdast::{Code, Node};
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, Theme, ThemeSet};
+use syntect::html::{
+    ClassStyle, IncludeBackground, css_for_theme_with_class_style, line_tokens_to_classed_spans,
+    styled_line_to_highlighted_html,
+};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Grammars and themes bundled in the binary, loaded on first use
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// How a single code block is to be shown, from its fence info string
+#[derive(Debug, Default, PartialEq)]
+struct FenceOptions {
+    /// Lines, starting at 1, to highlight like in ```` ```rust {1,3-5} ````
+    highlighted_lines: BTreeSet<usize>,
+    /// `linenos` or `nolinenos` in the info string, overriding the site setting
+    line_numbers: Option<bool>,
+}
+
+/// Reads the line ranges between braces and the `linenos`/`nolinenos` flags
+/// following the language of a fence. Anything else is ignored.
+fn parse_fence_options(meta: &str) -> FenceOptions {
+    let mut options = FenceOptions::default();
+    let mut rest = meta;
+    while !rest.is_empty() {
+        if let Some(after_brace) = rest.strip_prefix('{') {
+            let (ranges, after_ranges) = after_brace.split_once('}').unwrap_or((after_brace, ""));
+            for range in ranges.split(',').map(str::trim) {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                if let (Ok(start), Ok(end)) = (start.trim().parse::<usize>(), end.trim().parse()) {
+                    options.highlighted_lines.extend(start..=end);
+                }
+            }
+            rest = after_ranges;
+            continue;
+        }
+        let end = rest.find(['{', ' ']).unwrap_or(rest.len()).max(1);
+        match rest[..end].trim() {
+            "linenos" => options.line_numbers = Some(true),
+            "nolinenos" => options.line_numbers = Some(false),
+            _ => {}
+        }
+        rest = &rest[end..];
+    }
+    options
+}
+
+/// Escapes text the same way the markdown renderer does, so highlighted
+/// blocks can be matched with the ones it wrote.
+fn escape_html(text: &str) -> String {
+    text.replace('\0', "\u{fffd}")
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn css_color(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// The classes `line_tokens_to_classed_spans` gives a scope, one per atom
+fn scope_classes(scope: Scope) -> String {
+    scope.build_string().replace('.', " ")
+}
+
+fn code_blocks(node: &Node, blocks: &mut Vec<Code>) {
+    match node {
+        Node::Code(code) => blocks.push(code.clone()),
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                code_blocks(child, blocks);
+            }
+        }
+    }
+}
+
+/// Highlights code blocks at build time with the grammars and themes
+/// bundled with syntect.
+pub struct Highlighter {
+    theme: &'static Theme,
+    style: HighlightStyle,
+    line_numbers: bool,
+}
+
+impl Highlighter {
+    /// `None` when highlighting is disabled in the site configuration
+    pub fn new(config: &HighlightConfig) -> Result<Option<Self>, BuildError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let theme = THEMES
+            .themes
+            .get(&config.theme)
+            .ok_or_else(|| BuildError::Config {
+                message: format!(
+                    "Unknown highlight theme {}, available themes are: {}",
+                    config.theme,
+                    THEMES.themes.keys().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            })?;
+        Ok(Some(Highlighter {
+            theme,
+            style: config.style.clone(),
+            line_numbers: config.line_numbers,
+        }))
+    }
+
+    /// Stylesheet for the `classes` style, with the colors of the theme
+    pub fn stylesheet(&self) -> String {
+        let mut css =
+            css_for_theme_with_class_style(self.theme, ClassStyle::Spaced).unwrap_or_default();
+        if let Some(background) = self.theme.settings.background {
+            css.push_str(&format!(
+                "pre.highlight {{\n background-color: {};\n}}\n",
+                css_color(background)
+            ));
+        }
+        if let Some(line_highlight) = self.theme.settings.line_highlight {
+            css.push_str(&format!(
+                "pre.highlight .line.highlighted {{\n display: inline-block;\n width: 100%;\n background-color: {};\n}}\n",
+                css_color(line_highlight)
+            ));
+        }
+        css.push_str(
+            ".line-number {\n user-select: none;\n opacity: 0.5;\n padding-right: 1em;\n}\n",
+        );
+        css
+    }
+
+    fn syntax(&self, lang: Option<&str>) -> &'static SyntaxReference {
+        lang.and_then(|lang| SYNTAXES.find_syntax_by_token(lang))
+            .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
+    }
+
+    /// Each line of `code` as HTML, without its line ending
+    fn highlight_lines(&self, code: &str, syntax: &SyntaxReference) -> Vec<String> {
+        let mut lines = Vec::new();
+        match self.style {
+            HighlightStyle::Inline => {
+                let mut highlighter = HighlightLines::new(syntax, self.theme);
+                for line in LinesWithEndings::from(code) {
+                    let html = highlighter
+                        .highlight_line(line, &SYNTAXES)
+                        .ok()
+                        .and_then(|regions| {
+                            styled_line_to_highlighted_html(&regions, IncludeBackground::No).ok()
+                        })
+                        .unwrap_or_else(|| escape_html(line));
+                    lines.push(html.replace(['\n', '\r'], ""));
+                }
+            }
+            HighlightStyle::Classes => {
+                let mut parse_state = ParseState::new(syntax);
+                let mut scope_stack = ScopeStack::new();
+                for line in LinesWithEndings::from(code) {
+                    // Spans still open from the previous line are reopened
+                    // so every line stands on its own
+                    let mut html = scope_stack
+                        .as_slice()
+                        .iter()
+                        .map(|scope| format!("<span class=\"{}\">", scope_classes(*scope)))
+                        .collect::<String>();
+                    let line_html = parse_state
+                        .parse_line(line, &SYNTAXES)
+                        .ok()
+                        .and_then(|ops| {
+                            line_tokens_to_classed_spans(
+                                line,
+                                &ops,
+                                ClassStyle::Spaced,
+                                &mut scope_stack,
+                            )
+                            .ok()
+                        })
+                        .map(|(html, _)| html)
+                        .unwrap_or_else(|| escape_html(line));
+                    html.push_str(&line_html);
+                    html = html.replace(['\n', '\r'], "");
+                    html.push_str(&"</span>".repeat(scope_stack.len()));
+                    lines.push(html);
+                }
+            }
+        }
+        lines
+    }
+
+    /// A highlighted `<pre>` block for `code`, `meta` being what follows the
+    /// language in the fence info string.
+    pub fn highlight(&self, code: &str, lang: Option<&str>, meta: Option<&str>) -> String {
+        // `rust{3-5}` has no space between the language and the lines
+        let (lang, lang_meta) = match lang.and_then(|lang| lang.split_once('{')) {
+            Some((lang, rest)) => (Some(lang), format!("{{{}", rest)),
+            None => (lang, String::new()),
+        };
+        let options = parse_fence_options(&format!("{} {}", lang_meta, meta.unwrap_or_default()));
+        let line_numbers = options.line_numbers.unwrap_or(self.line_numbers);
+        let syntax = self.syntax(lang.filter(|lang| !lang.is_empty()));
+
+        let mut html = match self.style {
+            HighlightStyle::Inline => {
+                let settings = &self.theme.settings;
+                format!(
+                    "<pre class=\"highlight\" style=\"background-color:{};color:{};\">",
+                    css_color(settings.background.unwrap_or(Color::WHITE)),
+                    css_color(settings.foreground.unwrap_or(Color::BLACK)),
+                )
+            }
+            HighlightStyle::Classes => "<pre class=\"highlight\">".to_string(),
+        };
+        match lang {
+            Some(lang) if !lang.is_empty() => {
+                html.push_str(&format!("<code class=\"language-{}\">", escape_html(lang)))
+            }
+            _ => html.push_str("<code>"),
+        }
+
+        for (index, line) in self.highlight_lines(code, syntax).iter().enumerate() {
+            let number = index + 1;
+            if options.highlighted_lines.contains(&number) {
+                match (&self.style, self.theme.settings.line_highlight) {
+                    (HighlightStyle::Inline, Some(color)) => html.push_str(&format!(
+                        "<span class=\"line highlighted\" style=\"display:inline-block;width:100%;background-color:{};\">",
+                        css_color(color)
+                    )),
+                    _ => html.push_str("<span class=\"line highlighted\">"),
+                }
+            } else {
+                html.push_str("<span class=\"line\">");
+            }
+            if line_numbers {
+                match self.style {
+                    HighlightStyle::Inline => html.push_str(&format!(
+                        "<span class=\"line-number\" style=\"user-select:none;opacity:0.5;padding-right:1em;\">{}</span>",
+                        number
+                    )),
+                    HighlightStyle::Classes => html.push_str(&format!(
+                        "<span class=\"line-number\">{}</span>",
+                        number
+                    )),
+                }
+            }
+            html.push_str(line);
+            html.push_str("</span>\n");
+        }
+        html.push_str("</code></pre>");
+        html
+    }
+
+    /// Replaces the code blocks of `html`, rendered from `markdown`, with
+    /// highlighted ones. The blocks are found through the markdown syntax tree,
+    /// which keeps the fence info string the HTML output drops, parsed with
+    /// the `options` the HTML was rendered with.
+    pub fn highlight_code_blocks(
+        &self,
+        html: &str,
+        markdown: &str,
+        options: &markdown::ParseOptions,
+    ) -> String {
+        let Ok(tree) = markdown::to_mdast(markdown, options) else {
+            return html.to_string();
+        };
+        let mut blocks = Vec::new();
+        code_blocks(&tree, &mut blocks);
+
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        for block in blocks {
+            let mut content = escape_html(&block.value);
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            let closing = format!("{}</code></pre>", content);
+
+            // The next block of the HTML with the same content, skipping any
+            // `<pre>` that doesn't come from a code block
+            let mut search_from = 0;
+            while let Some(start) = rest[search_from..]
+                .find("<pre><code")
+                .map(|i| i + search_from)
+            {
+                let code_start = start + "<pre>".len();
+                let Some(content_start) = rest[code_start..].find('>').map(|i| code_start + i + 1)
+                else {
+                    break;
+                };
+                if rest[content_start..].starts_with(&closing) {
+                    output.push_str(&rest[..start]);
+                    output.push_str(&self.highlight(
+                        &block.value,
+                        block.lang.as_deref(),
+                        block.meta.as_deref(),
+                    ));
+                    rest = &rest[content_start + closing.len()..];
+                    break;
+                }
+                search_from = content_start;
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn highlighter(style: HighlightStyle, line_numbers: bool) -> Highlighter {
+        Highlighter::new(&HighlightConfig {
+            enabled: true,
+            style,
+            line_numbers,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_fence_options() {
+        assert_eq!(parse_fence_options(""), FenceOptions::default());
+        assert_eq!(
+            parse_fence_options("{1,3-5} linenos"),
+            FenceOptions {
+                highlighted_lines: BTreeSet::from([1, 3, 4, 5]),
+                line_numbers: Some(true),
+            }
+        );
+        assert_eq!(
+            parse_fence_options("nolinenos {2}"),
+            FenceOptions {
+                highlighted_lines: BTreeSet::from([2]),
+                line_numbers: Some(false),
+            }
+        );
+        assert_eq!(
+            parse_fence_options("{x-y} title=\"main.rs\""),
+            FenceOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_unknown_theme() {
+        let result = Highlighter::new(&HighlightConfig {
+            enabled: true,
+            theme: "Nope".to_string(),
+            ..Default::default()
+        });
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .starts_with("Unknown highlight theme Nope, available themes are: ")
+        );
+        let disabled = Highlighter::new(&HighlightConfig::default());
+        assert!(disabled.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_highlight_classes() {
+        let html = highlighter(HighlightStyle::Classes, false).highlight(
+            "/* a\nb */\nlet x = 1;",
+            Some("rust"),
+            Some("{2}"),
+        );
+        let lines = html
+            .strip_prefix("<pre class=\"highlight\"><code class=\"language-rust\">")
+            .and_then(|html| html.strip_suffix("\n</code></pre>"))
+            .unwrap()
+            .lines()
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        // The comment spans two lines, each line closes and reopens its spans
+        assert!(lines[0].starts_with("<span class=\"line\"><span class=\"source rust\">"));
+        assert!(lines[1].starts_with(
+            "<span class=\"line highlighted\"><span class=\"source rust\"><span class=\"comment block rust\">b"
+        ));
+        for line in &lines {
+            assert_eq!(
+                line.matches("<span").count(),
+                line.matches("</span>").count()
+            );
+        }
+        assert!(lines[2].ends_with("</span></span>"));
+    }
+
+    #[test]
+    fn test_highlight_inline_with_line_numbers() {
+        let html = highlighter(HighlightStyle::Inline, true).highlight("a < b", None, None);
+        assert!(html.starts_with("<pre class=\"highlight\" style=\"background-color:#ffffff;"));
+        assert!(html.contains("<code><span class=\"line\"><span class=\"line-number\""));
+        assert!(html.contains(">1</span>"));
+        assert!(html.contains("a &lt; b"));
+
+        let html =
+            highlighter(HighlightStyle::Inline, true).highlight("a", None, Some("nolinenos"));
+        assert!(!html.contains("line-number"));
+    }
+
+    #[test]
+    fn test_highlight_code_blocks() {
+        let markdown =
+            "Text\n\n```rust {1}\nfn main() {}\n```\n\n    indented\n\n> ```\n> quoted\n> ```\n";
+        let html = markdown::to_html(markdown);
+        let highlighted = highlighter(HighlightStyle::Classes, false).highlight_code_blocks(
+            &html,
+            markdown,
+            &markdown::ParseOptions::default(),
+        );
+
+        assert!(highlighted.starts_with("<p>Text</p>\n<pre class=\"highlight\"><code class=\"language-rust\"><span class=\"line highlighted\">"));
+        assert!(highlighted.contains("<pre class=\"highlight\"><code><span class=\"line\"><span class=\"text plain\">indented</span></span>\n</code></pre>"));
+        assert!(highlighted.contains("<blockquote>\n<pre class=\"highlight\"><code>"));
+        assert!(!highlighted.contains("<pre><code"));
+    }
+
+    #[test]
+    fn test_stylesheet() {
+        let css = highlighter(HighlightStyle::Classes, false).stylesheet();
+        assert!(css.contains(".source"));
+        assert!(css.contains("pre.highlight {"));
+        assert!(css.contains(".line-number {"));
+    }
+}
@@ -0,0 +1,881 @@
+use crate::content_router::slugify;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use minijinja::{Environment, State, Value};
+
+/// Liquid filters with a MiniJinja built-in doing the same under another name
+const RENAMED_FILTERS: [(&str, &str); 8] = [
+    ("upcase", "upper"),
+    ("downcase", "lower"),
+    ("strip", "trim"),
+    ("size", "length"),
+    ("jsonify", "tojson"),
+    ("uniq", "unique"),
+    ("url_encode", "urlencode"),
+    ("xml_escape", "escape"),
+];
+
+/// Liquid filters that MiniJinja has under the same name, or that
+/// `register_filters` adds
+const SUPPORTED_FILTERS: [&str; 28] = [
+    "abs",
+    "capitalize",
+    "default",
+    "escape",
+    "first",
+    "join",
+    "last",
+    "replace",
+    "reverse",
+    "round",
+    "sort",
+    "split",
+    "where",
+    "append",
+    "prepend",
+    "plus",
+    "minus",
+    "times",
+    "divided_by",
+    "modulo",
+    "remove",
+    "date",
+    "relative_url",
+    "absolute_url",
+    "slugify",
+    "strip_html",
+    "strip_newlines",
+    "truncate",
+];
+
+/// A part of a Liquid template
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    /// `{{ ... }}`, without the delimiters
+    Output {
+        inner: &'a str,
+        line: usize,
+    },
+    /// `{% ... %}`, without the delimiters
+    Tag {
+        inner: &'a str,
+        line: usize,
+    },
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    // Where to look for the next tag, braces before it being plain text
+    let mut search_from = 0;
+    while let Some(start) = rest[search_from..].find('{').map(|i| search_from + i) {
+        let (closing, is_tag) = match rest[start..].get(..2) {
+            Some("{{") => ("}}", false),
+            Some("{%") => ("%}", true),
+            _ => {
+                search_from = start + 1;
+                continue;
+            }
+        };
+        let Some(end) = rest[start + 2..].find(closing).map(|i| start + 2 + i) else {
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+            line += rest[..start].matches('\n').count();
+        }
+        let inner = &rest[start + 2..end];
+        tokens.push(if is_tag {
+            Token::Tag { inner, line }
+        } else {
+            Token::Output { inner, line }
+        });
+        line += inner.matches('\n').count();
+        rest = &rest[end + 2..];
+        search_from = 0;
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    tokens
+}
+
+/// Splits `text` on `separator` where it is not inside a quoted string
+fn split_outside_quotes<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let c = text[index..].chars().next().unwrap();
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if text[index..].starts_with(separator) => {
+                parts.push(&text[start..index]);
+                index += separator.len();
+                start = index;
+                continue;
+            }
+            None => {}
+        }
+        index += c.len_utf8();
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Translates a Liquid value: `nil`, `forloop` and `(1..5)` ranges
+fn translate_value(value: &str) -> String {
+    let value = value.trim();
+    if let Some(range) = value.strip_prefix('(').and_then(|v| v.strip_suffix(')'))
+        && let Some((start, end)) = range.split_once("..")
+    {
+        return format!(
+            "range({}, {} + 1)",
+            translate_value(start),
+            translate_value(end)
+        );
+    }
+
+    let mut translated = String::with_capacity(value.len());
+    let mut quote = None;
+    let mut chars = value.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match quote {
+            Some(q) => {
+                translated.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                translated.push(c);
+            }
+            None if c.is_alphabetic() || c == '_' => {
+                let mut end = index + c.len_utf8();
+                while let Some((next, n)) = chars.peek() {
+                    if n.is_alphanumeric() || *n == '_' || *n == '.' || *n == '-' {
+                        end = next + n.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let word = &value[index..end];
+                translated.push_str(&match word {
+                    "nil" => "none".to_string(),
+                    _ => match word.strip_prefix("forloop.") {
+                        Some(attribute) => {
+                            format!("loop.{}", attribute.replace("rindex", "revindex"))
+                        }
+                        None => word.to_string(),
+                    },
+                });
+            }
+            None => translated.push(c),
+        }
+    }
+    translated
+}
+
+/// Translates `value | filter: arg, arg`, failing on unknown filters
+fn translate_expression(expression: &str) -> Result<String, String> {
+    let mut parts = split_outside_quotes(expression, "|").into_iter();
+    let mut translated = translate_value(parts.next().unwrap_or_default());
+    for filter in parts {
+        let (name, arguments) = match split_outside_quotes(filter, ":").as_slice() {
+            [name] => (name.trim(), None),
+            [name, ..] => (name.trim(), Some(filter[name.len() + 1..].trim())),
+            [] => unreachable!(),
+        };
+        let name = match RENAMED_FILTERS.iter().find(|(liquid, _)| *liquid == name) {
+            Some((_, renamed)) => *renamed,
+            None if SUPPORTED_FILTERS.contains(&name) => name,
+            None => return Err(format!("Unsupported Liquid filter {}", name)),
+        };
+        translated.push_str(" | ");
+        translated.push_str(name);
+        if let Some(arguments) = arguments {
+            let arguments = split_outside_quotes(arguments, ",")
+                .into_iter()
+                .map(translate_value)
+                .collect::<Vec<_>>();
+            translated.push_str(&format!("({})", arguments.join(", ")));
+        }
+    }
+    Ok(translated)
+}
+
+/// Translates a condition, rewriting `a contains b` as `b in a`
+fn translate_condition(condition: &str) -> Result<String, String> {
+    let mut translated = Vec::new();
+    for (index, clause) in split_outside_quotes(condition, " or ")
+        .into_iter()
+        .enumerate()
+    {
+        if index > 0 {
+            translated.push("or".to_string());
+        }
+        for (index, clause) in split_outside_quotes(clause, " and ")
+            .into_iter()
+            .enumerate()
+        {
+            if index > 0 {
+                translated.push("and".to_string());
+            }
+            match split_outside_quotes(clause, " contains ").as_slice() {
+                [haystack, needle] => translated.push(format!(
+                    "({} in {})",
+                    translate_expression(needle)?,
+                    translate_expression(haystack)?
+                )),
+                _ => translated.push(translate_expression(clause)?),
+            }
+        }
+    }
+    Ok(translated.join(" "))
+}
+
+/// `for item in items limit:2 offset:1 reversed`
+fn translate_for(arguments: &str) -> Result<String, String> {
+    let (variable, rest) = arguments
+        .split_once(" in ")
+        .ok_or_else(|| "Invalid Liquid for loop".to_string())?;
+    let rest = rest.replace(": ", ":");
+    let mut parts = rest.split_whitespace();
+    let mut collection = translate_value(parts.next().unwrap_or_default());
+    let (mut limit, mut offset) = (None, None);
+    for parameter in parts {
+        match parameter.split_once(':') {
+            Some(("limit", value)) => limit = Some(translate_value(value)),
+            Some(("offset", value)) => offset = Some(translate_value(value)),
+            None if parameter == "reversed" => collection = format!("({} | reverse)", collection),
+            _ => return Err(format!("Unsupported Liquid for parameter {}", parameter)),
+        }
+    }
+    if limit.is_some() || offset.is_some() {
+        let offset = offset.unwrap_or("0".to_string());
+        let end = limit
+            .map(|limit| format!("{} + {}", offset, limit))
+            .unwrap_or_default();
+        collection = format!("{}[{}:{}]", collection, offset, end);
+    }
+    Ok(format!("for {} in {}", variable.trim(), collection))
+}
+
+/// `include note.html content="Hi"`, the parameters being exposed to the
+/// included template as `include.content` like Jekyll does.
+fn translate_include(arguments: &str) -> Result<String, String> {
+    let parts = split_outside_quotes(arguments.trim(), " ")
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    let Some((name, parameters)) = parts.split_first() else {
+        return Err("Missing template in Liquid include".to_string());
+    };
+    if name.contains("{{") {
+        return Err("Unsupported Liquid include with a variable template".to_string());
+    }
+    let name = name.trim_matches(['"', '\'']);
+    if parameters.is_empty() {
+        return Ok(format!("{{% include \"{}\" %}}", name));
+    }
+
+    let mut values = Vec::new();
+    for parameter in parameters {
+        let (key, value) = parameter
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid Liquid include parameter {}", parameter))?;
+        values.push(format!("\"{}\": {}", key, translate_value(value)));
+    }
+    Ok(format!(
+        "{{% with include = {{{}}} %}}{{% include \"{}\" %}}{{% endwith %}}",
+        values.join(", "),
+        name
+    ))
+}
+
+/// Opening fence of a code block for `highlight ruby linenos hl_lines="2 3"`
+fn translate_highlight(arguments: &str) -> String {
+    let mut parts = split_outside_quotes(arguments.trim(), " ").into_iter();
+    let mut fence = format!("```{}", parts.next().unwrap_or_default());
+    for option in parts {
+        match option.split_once('=') {
+            Some(("hl_lines", lines)) => {
+                let lines = lines.trim_matches(['"', '\'']).split_whitespace();
+                fence.push_str(&format!(" {{{}}}", lines.collect::<Vec<_>>().join(",")));
+            }
+            _ if option == "linenos" => fence.push_str(" linenos"),
+            _ => {}
+        }
+    }
+    fence
+}
+
+fn ensure_new_line(output: &mut String) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+/// Keeps `text` out of the reach of MiniJinja
+fn raw(text: &str) -> String {
+    format!("{{% raw %}}{}{{% endraw %}}", text)
+}
+
+/// What a block being translated needs at its end
+enum Block {
+    Case {
+        subject: String,
+        has_when: bool,
+    },
+    /// Variables assigned in the loop, with the line of their assignment
+    For {
+        assigned: Vec<(String, usize)>,
+    },
+    Other,
+}
+
+/// Names that `content` may refer to
+fn identifiers(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+}
+
+/// Translates the Jekyll flavour of Liquid in `source` to MiniJinja. Tags and
+/// filters that can't be translated are kept as text, each with a warning
+/// naming its line.
+pub fn translate_liquid(source: &str) -> (String, Vec<(usize, String)>) {
+    let mut output = String::with_capacity(source.len());
+    let mut warnings = Vec::new();
+    let mut blocks = Vec::new();
+    // Variables assigned in a loop that ended: unlike Liquid, MiniJinja
+    // doesn't keep them after the loop
+    let mut loop_assigned: Vec<(String, usize)> = Vec::new();
+    // Set right after a `highlight` tag, whose code starts on the next line
+    let mut in_fence_opening = false;
+
+    let mut tokens = tokenize(source).into_iter();
+    while let Some(token) = tokens.next() {
+        let (inner, line, is_tag) = match token {
+            Token::Text(text) => {
+                let text = match in_fence_opening {
+                    true => text.strip_prefix('\n').unwrap_or(text),
+                    false => text,
+                };
+                in_fence_opening = false;
+                // Like Liquid, nothing is output between `case` and its first `when`
+                if let Some(Block::Case {
+                    has_when: false, ..
+                }) = blocks.last()
+                {
+                    continue;
+                }
+                // `{#` opens a comment in MiniJinja but not in Liquid
+                output.push_str(&text.replace("{#", "{{ \"{#\" }}"));
+                continue;
+            }
+            Token::Output { inner, line } => (inner, line, false),
+            Token::Tag { inner, line } => (inner, line, true),
+        };
+        in_fence_opening = false;
+        let trim_left = if inner.starts_with('-') { "-" } else { "" };
+        let trim_right = if inner.ends_with('-') { "-" } else { "" };
+        let content = inner.trim_matches('-').trim();
+        let original = if is_tag {
+            format!("{{%{}%}}", inner)
+        } else {
+            format!("{{{{{}}}}}", inner)
+        };
+
+        let (name, arguments) = content
+            .split_once(char::is_whitespace)
+            .unwrap_or((content, ""));
+        let arguments = arguments.trim();
+
+        let (assigned, used) = match (is_tag, name) {
+            (true, "assign") => match arguments.split_once('=') {
+                Some((variable, value)) => (Some(variable.trim()), value),
+                None => (None, arguments),
+            },
+            (true, "capture") => (Some(arguments), ""),
+            _ => (None, content),
+        };
+        let used = identifiers(used).collect::<Vec<_>>();
+        loop_assigned.retain(|(variable, assignment_line)| {
+            if !used.contains(&variable.as_str()) {
+                return true;
+            }
+            warnings.push((
+                line,
+                format!(
+                    "{} is assigned in the for loop of line {}, which MiniJinja doesn't keep after the loop",
+                    variable, assignment_line
+                ),
+            ));
+            false
+        });
+        if let Some(assigned) = assigned {
+            loop_assigned.retain(|(variable, _)| variable != assigned);
+            let innermost_loop = blocks.iter_mut().rev().find_map(|block| match block {
+                Block::For { assigned } => Some(assigned),
+                _ => None,
+            });
+            if let Some(loop_assigned) = innermost_loop
+                && !loop_assigned
+                    .iter()
+                    .any(|(variable, _)| variable == assigned)
+            {
+                loop_assigned.push((assigned.to_string(), line));
+            }
+        }
+
+        if !is_tag {
+            match translate_expression(content) {
+                Ok(expression) => output.push_str(&format!(
+                    "{{{{{} {} {}}}}}",
+                    trim_left, expression, trim_right
+                )),
+                Err(message) => {
+                    warnings.push((line, message));
+                    output.push_str(&raw(&original));
+                }
+            }
+            continue;
+        }
+
+        let tag = |translated: &str| format!("{{%{} {} {}%}}", trim_left, translated, trim_right);
+        let translated = match name {
+            "raw" => {
+                // Copied as is up to the matching `endraw`
+                let mut text = String::new();
+                for token in tokens.by_ref() {
+                    match token {
+                        Token::Tag { inner, .. } if inner.trim_matches('-').trim() == "endraw" => {
+                            break;
+                        }
+                        Token::Text(part) => text.push_str(part),
+                        Token::Output { inner, .. } => text.push_str(&format!("{{{{{}}}}}", inner)),
+                        Token::Tag { inner, .. } => text.push_str(&format!("{{%{}%}}", inner)),
+                    }
+                }
+                Ok(raw(&text))
+            }
+            "comment" => {
+                for token in tokens.by_ref() {
+                    if matches!(token, Token::Tag { inner, .. } if inner.trim_matches('-').trim() == "endcomment")
+                    {
+                        break;
+                    }
+                }
+                Ok(String::new())
+            }
+            "highlight" => {
+                ensure_new_line(&mut output);
+                in_fence_opening = true;
+                Ok(format!("{}\n", translate_highlight(arguments)))
+            }
+            "endhighlight" => {
+                ensure_new_line(&mut output);
+                Ok("```".to_string())
+            }
+            "if" => {
+                blocks.push(Block::Other);
+                translate_condition(arguments).map(|condition| tag(&format!("if {}", condition)))
+            }
+            "unless" => {
+                blocks.push(Block::Other);
+                translate_condition(arguments)
+                    .map(|condition| tag(&format!("if not ({})", condition)))
+            }
+            "elsif" => {
+                translate_condition(arguments).map(|condition| tag(&format!("elif {}", condition)))
+            }
+            "for" => {
+                blocks.push(Block::For {
+                    assigned: Vec::new(),
+                });
+                translate_for(arguments).map(|for_loop| tag(&for_loop))
+            }
+            "capture" => {
+                blocks.push(Block::Other);
+                Ok(tag(&format!("set {}", arguments)))
+            }
+            "case" => {
+                blocks.push(Block::Case {
+                    subject: translate_value(arguments),
+                    has_when: false,
+                });
+                Ok(String::new())
+            }
+            "when" => match blocks.last_mut() {
+                Some(Block::Case { subject, has_when }) => {
+                    let keyword = if *has_when { "elif" } else { "if" };
+                    *has_when = true;
+                    let values = split_outside_quotes(arguments, ",")
+                        .into_iter()
+                        .flat_map(|value| split_outside_quotes(value, " or "))
+                        .map(|value| format!("{} == {}", subject, translate_value(value)))
+                        .collect::<Vec<_>>();
+                    Ok(tag(&format!("{} {}", keyword, values.join(" or "))))
+                }
+                _ => Err("Liquid when outside of a case".to_string()),
+            },
+            "else" => match blocks.last() {
+                Some(Block::Case {
+                    has_when: false, ..
+                }) => {
+                    warnings.push((
+                        line,
+                        "Liquid else before the first when of a case is left out".to_string(),
+                    ));
+                    continue;
+                }
+                _ => Ok(tag("else")),
+            },
+            "endif" | "endunless" | "endfor" | "endcapture" | "endcase" => match blocks.pop() {
+                Some(Block::Case {
+                    has_when: false, ..
+                }) => Ok(String::new()),
+                Some(Block::Case { .. }) => Ok(tag("endif")),
+                Some(Block::For { assigned }) => {
+                    loop_assigned.extend(assigned);
+                    Ok(tag("endfor"))
+                }
+                _ => Ok(tag(match name {
+                    "endfor" => "endfor",
+                    "endcapture" => "endset",
+                    _ => "endif",
+                })),
+            },
+            "assign" => match arguments.split_once('=') {
+                Some((variable, value)) => translate_expression(value)
+                    .map(|value| tag(&format!("set {} = {}", variable.trim(), value))),
+                None => Err("Invalid Liquid assign".to_string()),
+            },
+            "include" => translate_include(arguments),
+            "break" | "continue" => Ok(tag(name)),
+            _ => Err(format!("Unsupported Liquid tag {}", name)),
+        };
+
+        match translated {
+            Ok(translated) => output.push_str(&translated),
+            Err(message) => {
+                warnings.push((line, message));
+                output.push_str(&raw(&original));
+            }
+        }
+    }
+
+    (output, warnings)
+}
+
+fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    match value {
+        "now" | "today" => Some(Local::now().naive_local()),
+        _ => DateTime::parse_from_rfc3339(value)
+            .map(|date| date.naive_local())
+            .ok()
+            .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            }),
+    }
+}
+
+fn number(value: &Value) -> f64 {
+    f64::try_from(value.clone())
+        .ok()
+        .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+        .unwrap_or_default()
+}
+
+/// Integer results stay integers, like in Liquid
+fn number_value(result: f64) -> Value {
+    if result.fract() == 0.0 && result.abs() < i64::MAX as f64 {
+        Value::from(result as i64)
+    } else {
+        Value::from(result)
+    }
+}
+
+fn site_base_url(state: &State) -> String {
+    state
+        .lookup("site")
+        .and_then(|site| site.get_attr("base_url").ok())
+        .filter(|base_url| !base_url.is_none() && !base_url.is_undefined())
+        .map(|base_url| base_url.to_string())
+        .unwrap_or_default()
+}
+
+/// Adds the Jekyll filters MiniJinja doesn't have to `env`
+pub fn register_filters(env: &mut Environment) {
+    env.add_filter("append", |value: String, suffix: String| value + &suffix);
+    env.add_filter("prepend", |value: String, prefix: String| prefix + &value);
+    env.add_filter("remove", |value: String, text: String| {
+        value.replace(&text, "")
+    });
+    env.add_filter("plus", |a: Value, b: Value| {
+        number_value(number(&a) + number(&b))
+    });
+    env.add_filter("minus", |a: Value, b: Value| {
+        number_value(number(&a) - number(&b))
+    });
+    env.add_filter("times", |a: Value, b: Value| {
+        number_value(number(&a) * number(&b))
+    });
+    env.add_filter("divided_by", |a: Value, b: Value| {
+        let (a, b) = (number(&a), number(&b));
+        match (a.fract() == 0.0 && b.fract() == 0.0, b == 0.0) {
+            (_, true) => Value::from(()),
+            (true, false) => number_value((a / b).floor()),
+            (false, false) => number_value(a / b),
+        }
+    });
+    env.add_filter("modulo", |a: Value, b: Value| {
+        number_value(number(&a) % number(&b))
+    });
+    env.add_filter("date", |value: Value, format: String| {
+        let text = value.to_string();
+        match parse_date(&text) {
+            Some(date) => date.format(&format).to_string(),
+            None => text,
+        }
+    });
+    env.add_filter("slugify", |value: String| slugify(&value));
+    env.add_filter("strip_newlines", |value: String| {
+        value.replace(['\n', '\r'], "")
+    });
+    env.add_filter("strip_html", |value: String| {
+        let mut text = String::with_capacity(value.len());
+        let mut in_tag = false;
+        for c in value.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' if in_tag => in_tag = false,
+                _ if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        text
+    });
+    env.add_filter(
+        "truncate",
+        |value: String, length: Option<usize>, ellipsis: Option<String>| {
+            let length = length.unwrap_or(50);
+            let ellipsis = ellipsis.unwrap_or("...".to_string());
+            if value.chars().count() <= length {
+                return value;
+            }
+            let kept = length.saturating_sub(ellipsis.chars().count());
+            value.chars().take(kept).collect::<String>() + &ellipsis
+        },
+    );
+    env.add_filter(
+        "where",
+        |items: Vec<Value>, key: String, expected: Value| {
+            items
+                .into_iter()
+                .filter(|item| item.get_attr(&key).is_ok_and(|value| value == expected))
+                .collect::<Vec<_>>()
+        },
+    );
+    env.add_filter("relative_url", |value: String| {
+        format!("/{}", value.trim_start_matches('/'))
+    });
+    env.add_filter("absolute_url", |state: &State, value: String| {
+        format!(
+            "{}/{}",
+            site_base_url(state).trim_end_matches('/'),
+            value.trim_start_matches('/')
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+    use pretty_assertions::assert_eq;
+
+    fn render(source: &str) -> String {
+        let (translated, warnings) = translate_liquid(source);
+        assert_eq!(warnings, vec![], "{}", translated);
+        let mut env = Environment::new();
+        register_filters(&mut env);
+        env.add_template("note.html", "Note: {{ include.text }}")
+            .unwrap();
+        env.render_str(
+            &translated,
+            context! {
+                site => context! { title => "Blog", base_url => "https://example.com/" },
+                page => context! { title => "Post", tags => vec!["rust", "web"] },
+                posts => vec!["a", "b", "c", "d"],
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("a {{ b }}\n{% if c %}{d}"),
+            vec![
+                Token::Text("a "),
+                Token::Output {
+                    inner: " b ",
+                    line: 1
+                },
+                Token::Text("\n"),
+                Token::Tag {
+                    inner: " if c ",
+                    line: 2
+                },
+                Token::Text("{d}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variables_and_filters() {
+        assert_eq!(render("{{ site.title | upcase }}"), "BLOG");
+        assert_eq!(
+            render("{{ page.title | append: \"!\" | prepend: '¡' }}"),
+            "¡Post!"
+        );
+        assert_eq!(render("{{ page.missing | default: \"none\" }}"), "none");
+        assert_eq!(
+            render("{{ page.tags | size }} {{ 10 | divided_by: 3 }}"),
+            "2 3"
+        );
+        assert_eq!(
+            render("{{ \"2025-05-04\" | date: \"%d/%m/%Y\" }}"),
+            "04/05/2025"
+        );
+        assert_eq!(
+            render("{{ \"/about/\" | absolute_url }}"),
+            "https://example.com/about/"
+        );
+        assert_eq!(render("{{ \"Hello World\" | slugify }}"), "hello-world");
+        assert_eq!(
+            render("{{ \"a|b\" | split: \"|\" | join: \", \" }}"),
+            "a, b"
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        assert_eq!(
+            render("{% if page.tags contains \"rust\" and site.title == 'Blog' %}yes{% endif %}"),
+            "yes"
+        );
+        assert_eq!(
+            render("{% unless page.draft %}published{% else %}draft{% endunless %}"),
+            "published"
+        );
+        assert_eq!(
+            render(
+                "{% for post in posts limit:2 offset:1 %}{{ forloop.index }}{{ post }}{% endfor %}"
+            ),
+            "1b2c"
+        );
+        assert_eq!(
+            render("{% for i in (1..3) reversed %}{{ i }}{% endfor %}"),
+            "321"
+        );
+        assert_eq!(
+            render(
+                "{% assign n = page.tags | size %}{% capture title %}{{ n }} tags{% endcapture %}{{ title }}"
+            ),
+            "2 tags"
+        );
+        assert_eq!(
+            render(
+                "{% case page.title %}\n{% when 'Draft' %}draft{% when \"Post\", 'Page' %}post{% else %}other{% endcase %}"
+            ),
+            "post"
+        );
+    }
+
+    #[test]
+    fn test_loop_assigns_and_stray_else() {
+        let (_, warnings) = translate_liquid(
+            "{% assign found = false %}\n{% for tag in page.tags %}\n\
+             {% assign found = true %}{% assign upper = tag | upcase %}{{ upper }}\n\
+             {% endfor %}\n{% if found %}found{% endif %}{{ found }}",
+        );
+        assert_eq!(
+            warnings,
+            vec![(
+                5,
+                "found is assigned in the for loop of line 3, which MiniJinja doesn't keep after the loop"
+                    .to_string()
+            )]
+        );
+
+        let (_, warnings) = translate_liquid(
+            "{% for tag in page.tags %}{% assign found = true %}{% endfor %}\
+             {% assign found = false %}{{ found }}",
+        );
+        assert!(warnings.is_empty());
+
+        let (translated, warnings) = translate_liquid(
+            "{% case page.title %}{% else %}skipped{% when 'Post' %}post{% endcase %}",
+        );
+        assert_eq!(
+            warnings,
+            vec![(
+                1,
+                "Liquid else before the first when of a case is left out".to_string()
+            )]
+        );
+        assert_eq!(translated, "{% if page.title == 'Post' %}post{% endif %}");
+    }
+
+    #[test]
+    fn test_raw_comment_and_include() {
+        assert_eq!(render("{% raw %}{{ kept }}{% endraw %}"), "{{ kept }}");
+        assert_eq!(render("a{% comment %}{{ hidden }}{% endcomment %}b"), "ab");
+        assert_eq!(render("{% include note.html text=\"Hi\" %}"), "Note: Hi");
+        assert_eq!(
+            render("Ruby's \"#{name}\" and {#"),
+            "Ruby's \"#{name}\" and {#"
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let (translated, warnings) = translate_liquid(
+            "Code:\n{% highlight ruby linenos hl_lines=\"2 3\" %}\nputs 1\n{% endhighlight %}\nDone",
+        );
+        assert!(warnings.is_empty());
+        assert_eq!(
+            translated,
+            "Code:\n```ruby linenos {2,3}\nputs 1\n```\nDone"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_tags_and_filters() {
+        let (translated, warnings) =
+            translate_liquid("{% post_url 2010-07-21-name %}\n{{ page.content | markdownify }}");
+        assert_eq!(
+            warnings,
+            vec![
+                (1, "Unsupported Liquid tag post_url".to_string()),
+                (2, "Unsupported Liquid filter markdownify".to_string()),
+            ]
+        );
+        assert_eq!(
+            translated,
+            "{% raw %}{% post_url 2010-07-21-name %}{% endraw %}\n{% raw %}{{ page.content | markdownify }}{% endraw %}"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::content_router::route_url;
+use crate::entities::Paginator;
+
+/// Route of the given page of the listing at `route`: the listing itself for
+/// the first page and `page/<number>/index.html` next to it for the others.
+pub fn page_route(route: &str, page: usize) -> String {
+    if page <= 1 {
+        return route.to_string();
+    }
+    let directory = match route.rfind('/') {
+        Some(index) => &route[..=index],
+        None => "",
+    };
+    format!("{}page/{}/index.html", directory, page)
+}
+
+/// Splits `items` in pages of `per_page` items for the listing at `route`.
+/// Without `per_page`, every item is on the first page. There is always at
+/// least one page, if only to say there is nothing to list.
+pub fn paginate<T: Clone>(items: &[T], per_page: Option<usize>, route: &str) -> Vec<Paginator<T>> {
+    let per_page = per_page
+        .filter(|per_page| *per_page > 0)
+        .unwrap_or(items.len())
+        .max(1);
+    let total_pages = items.len().div_ceil(per_page).max(1);
+    let url = |page| route_url(&page_route(route, page));
+
+    (1..=total_pages)
+        .map(|page| Paginator {
+            page,
+            total_pages,
+            per_page,
+            total_items: items.len(),
+            items: items
+                .iter()
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .cloned()
+                .collect(),
+            route: page_route(route, page),
+            url: url(page),
+            previous_url: (page > 1).then(|| url(page - 1)),
+            next_url: (page < total_pages).then(|| url(page + 1)),
+            first_url: url(1),
+            last_url: url(total_pages),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_page_route() {
+        assert_eq!(page_route("index.html", 1), "index.html");
+        assert_eq!(page_route("index.html", 2), "page/2/index.html");
+        assert_eq!(
+            page_route("tags/rust/index.html", 3),
+            "tags/rust/page/3/index.html"
+        );
+    }
+
+    #[test]
+    fn test_paginate() {
+        let pages = paginate(&[1, 2, 3, 4, 5], Some(2), "tags/rust/index.html");
+        assert_eq!(pages.len(), 3);
+        assert_eq!(
+            pages[1],
+            Paginator {
+                page: 2,
+                total_pages: 3,
+                per_page: 2,
+                total_items: 5,
+                items: vec![3, 4],
+                route: "tags/rust/page/2/index.html".to_string(),
+                url: "/tags/rust/page/2/".to_string(),
+                previous_url: Some("/tags/rust/".to_string()),
+                next_url: Some("/tags/rust/page/3/".to_string()),
+                first_url: "/tags/rust/".to_string(),
+                last_url: "/tags/rust/page/3/".to_string(),
+            }
+        );
+        assert_eq!(pages[0].previous_url, None);
+        assert_eq!(pages[2].items, vec![5]);
+        assert_eq!(pages[2].next_url, None);
+    }
+
+    #[test]
+    fn test_paginate_single_page() {
+        let pages = paginate(&[1, 2, 3], None, "index.html");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].items, vec![1, 2, 3]);
+        assert_eq!(pages[0].url, "/");
+        assert_eq!(pages[0].next_url, None);
+
+        let pages = paginate::<usize>(&[], Some(10), "index.html");
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].items, Vec::<usize>::new());
+        assert_eq!(pages[0].total_pages, 1);
+    }
+}
@@ -1,92 +1,347 @@
-use crate::entities::{ContentKind, Metadata, RawPost, SiteConfig};
-use chrono::NaiveDate;
-use std::collections::HashMap;
+use crate::content_router::slugify;
+use crate::entities::{ContentKind, FrontMatter, Metadata, RawPost, SiteConfig};
+use crate::errors::BuildError;
+use chrono::{NaiveDate, NaiveTime};
+use serde_yaml::Value;
 use std::fs;
 use std::path::Path;
 
-fn parse_front_matter(lines: Vec<String>) -> Result<HashMap<String, String>, String> {
-    let mut front_matter = HashMap::new();
+fn front_matter_error(og_path: &str, line: Option<usize>, message: String) -> BuildError {
+    BuildError::FrontMatter {
+        path: og_path.to_string(),
+        line,
+        message,
+    }
+}
+
+fn without_nulls(front_matter: FrontMatter) -> FrontMatter {
+    front_matter
+        .into_iter()
+        .filter(|(_, value)| !value.is_null())
+        .collect()
+}
 
-    for line in lines {
-        let parts = line.splitn(2, ':').collect::<Vec<&str>>();
-        let key = parts[0].trim();
-        let value = parts[1].trim();
-        if !value.is_empty() {
-            front_matter.insert(key.to_string(), value.to_string());
+// The front matter of YAML and TOML posts starts on the second line, right
+// after the opening delimiter, so lines reported by the parsers are shifted
+fn parse_front_matter(og_path: &str, lines: Vec<String>) -> Result<FrontMatter, BuildError> {
+    let front_matter = lines.join("\n");
+    let front_matter: Option<FrontMatter> = serde_yaml::from_str(&front_matter).map_err(|e| {
+        front_matter_error(
+            og_path,
+            e.location().map(|location| location.line() + 1),
+            format!("Invalid front matter: {}", e),
+        )
+    })?;
+
+    Ok(without_nulls(front_matter.unwrap_or_default()))
+}
+
+fn toml_to_yaml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::String(value),
+        toml::Value::Integer(value) => Value::Number(value.into()),
+        toml::Value::Float(value) => Value::Number(value.into()),
+        toml::Value::Boolean(value) => Value::Bool(value),
+        toml::Value::Datetime(value) => Value::String(value.to_string()),
+        toml::Value::Array(values) => {
+            Value::Sequence(values.into_iter().map(toml_to_yaml).collect())
         }
+        toml::Value::Table(table) => Value::Mapping(
+            table
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), toml_to_yaml(value)))
+                .collect(),
+        ),
     }
+}
 
-    Ok(front_matter)
+fn parse_toml_front_matter(og_path: &str, lines: Vec<String>) -> Result<FrontMatter, BuildError> {
+    let front_matter = lines.join("\n");
+    let parsed: toml::Table = toml::from_str(&front_matter).map_err(|e| {
+        front_matter_error(
+            og_path,
+            e.span()
+                .map(|span| front_matter[..span.start].matches('\n').count() + 2),
+            format!("Invalid front matter: {}", e.message()),
+        )
+    })?;
+
+    Ok(parsed
+        .into_iter()
+        .map(|(key, value)| (key, toml_to_yaml(value)))
+        .collect())
 }
 
-pub fn parse_content(og_path: &str, content: &str) -> Result<RawPost, String> {
-    let mut front_matter = Vec::<String>::new();
-    let mut body = Vec::<String>::new();
+fn parse_json_front_matter(
+    og_path: &str,
+    content: &str,
+) -> Result<(FrontMatter, String), BuildError> {
+    let mut stream = serde_json::Deserializer::from_str(content)
+        .into_iter::<serde_json::Map<String, serde_json::Value>>();
+    let front_matter = match stream.next() {
+        Some(front_matter) => front_matter.map_err(|e| {
+            front_matter_error(
+                og_path,
+                Some(e.line()),
+                format!("Invalid front matter: {}", e),
+            )
+        })?,
+        None => {
+            return Err(front_matter_error(
+                og_path,
+                None,
+                "No front matter found".to_string(),
+            ));
+        }
+    };
 
-    let mut lines = content.lines();
+    let front_matter = front_matter
+        .into_iter()
+        .map(|(key, value)| Ok((key, serde_yaml::to_value(value)?)))
+        .collect::<Result<FrontMatter, serde_yaml::Error>>()
+        .map_err(|e| front_matter_error(og_path, None, format!("Invalid front matter: {}", e)))?;
 
-    let Some(first_line) = lines.next() else {
-        return Err("The content is empt".to_string());
-    };
+    // Whatever follows the closing brace on its line is not part of the body
+    let rest = &content[stream.byte_offset()..];
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
 
-    if !first_line.starts_with("---") {
-        return Err("No front matter found".to_string());
-    }
+    Ok((without_nulls(front_matter), body.to_string()))
+}
 
-    let mut is_in_front_matter = true;
+fn split_front_matter(
+    og_path: &str,
+    content: &str,
+    delimiter: &str,
+) -> Result<(Vec<String>, String), BuildError> {
+    let mut front_matter = Vec::<String>::new();
+    let mut offset = 0;
 
-    for line in lines {
-        if line.starts_with("---") {
-            is_in_front_matter = false;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        if index == 0 {
             continue;
         }
 
-        if is_in_front_matter {
-            front_matter.push(line.to_string());
-        } else {
-            body.push(line.to_string());
+        if line.trim_end() == delimiter {
+            // Only the first closing delimiter counts, the rest of the
+            // content is the body exactly as written
+            return Ok((front_matter, content[offset..].to_string()));
         }
+
+        front_matter.push(line.trim_end_matches(['\r', '\n']).to_string());
     }
 
+    Err(front_matter_error(
+        og_path,
+        None,
+        format!(
+            "Front matter is not closed, expected a line with `{}`",
+            delimiter
+        ),
+    ))
+}
+
+fn value_to_string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(value.to_string()),
+        _ => Err(format!("{} must be a single value", key)),
+    }
+}
+
+fn value_to_bool(key: &str, value: Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(value) => Ok(value),
+        Value::String(value) if value == "true" || value == "false" => Ok(value == "true"),
+        _ => Err(format!("{} must be true or false", key)),
+    }
+}
+
+/// Whether `time` is what Jekyll allows after a date: a time like `10:00`
+/// or `10:00:00.5`, then an optional `Z` or offset like `+0200` or `-05:00`
+fn is_time(time: &str) -> bool {
+    let Ok((_, rest)) = NaiveTime::parse_and_remainder(time, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_and_remainder(time, "%H:%M"))
+    else {
+        return false;
+    };
+    let offset = rest.trim_start();
+    offset.is_empty()
+        || offset == "Z"
+        || (offset.starts_with(['+', '-'])
+            && matches!(offset[1..].len(), 4 | 5)
+            && offset[1..]
+                .chars()
+                .enumerate()
+                .all(|(i, c)| c.is_ascii_digit() || (i == 2 && c == ':')))
+}
+
+/// Date of a `YYYY-MM-DD` value, the time Jekyll allows after it being left
+/// aside: `2025-05-04 10:00:00 +0200` is May 4th, 2025
+fn value_to_date(key: &str, value: Value) -> Result<NaiveDate, String> {
+    let date_str = value_to_string(key, value)?;
+    let (date, time) = NaiveDate::parse_and_remainder(date_str.trim(), "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date format: {}. Expected YYYY-MM-DD", e))?;
+    match time.strip_prefix([' ', 'T']) {
+        None if time.is_empty() => Ok(date),
+        Some(time) if is_time(time.trim_start()) => Ok(date),
+        _ => Err(format!(
+            "Invalid date format: {}. Expected YYYY-MM-DD, optionally followed by a time",
+            date_str
+        )),
+    }
+}
+
+/// A slug given in the front matter, which must be usable as a single URL
+/// segment
+fn parse_slug(slug: Value) -> Result<String, String> {
+    let slug = value_to_string("Slug", slug)?.trim().to_string();
+    if slug.is_empty() || slug == "." || slug == ".." || slug.contains(['/', '\\']) {
+        return Err(format!("Slug {:?} can't be used in a URL", slug));
+    }
+    Ok(slug)
+}
+
+fn value_to_list(key: &str, value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Sequence(values) => values
+            .into_iter()
+            .map(|value| value_to_string(key, value))
+            .collect(),
+        value => Ok(value_to_string(key, value)?
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()),
+    }
+}
+
+/// Splits a post into its front matter and body. The front matter can be
+/// YAML fenced by `---`, TOML fenced by `+++` or a leading JSON object.
+pub fn parse_content(og_path: &str, content: &str) -> Result<RawPost, BuildError> {
+    let Some(first_line) = content.lines().next() else {
+        return Err(front_matter_error(
+            og_path,
+            None,
+            "The content is empty".to_string(),
+        ));
+    };
+
+    let (front_matter, body) = if first_line.starts_with("---") {
+        let (front_matter, body) = split_front_matter(og_path, content, "---")?;
+        (parse_front_matter(og_path, front_matter)?, body)
+    } else if first_line.starts_with("+++") {
+        let (front_matter, body) = split_front_matter(og_path, content, "+++")?;
+        (parse_toml_front_matter(og_path, front_matter)?, body)
+    } else if first_line.starts_with('{') {
+        parse_json_front_matter(og_path, content)?
+    } else {
+        return Err(front_matter_error(
+            og_path,
+            Some(1),
+            "No front matter found".to_string(),
+        ));
+    };
+
     Ok(RawPost {
-        front_matter: parse_front_matter(front_matter).unwrap(),
-        body: body.join("\n"),
+        front_matter,
+        body_line: content[..content.len() - body.len()].matches('\n').count() + 1,
+        body,
         og_path: og_path.to_string(),
         kind: ContentKind::Markdown, // TODO: Currently only markdown is supported
     })
 }
 
-pub fn parse_metadata(front_matter: HashMap<String, String>) -> Result<Metadata, String> {
-    let mut front_matter = front_matter.clone();
-    let title = front_matter.remove("title").ok_or("Title is required")?;
-    let description = front_matter
-        .remove("description")
-        .ok_or("Description is required")?;
-    let date_str = front_matter.remove("date").ok_or("Date is required")?;
-    let tags_str = front_matter.remove("tags").ok_or("Tags are required")?;
+/// Splits a Jekyll style file name, `YYYY-MM-DD-title`, into its date and
+/// the rest of the name. `dir/index.md` uses the name of the directory.
+fn parse_file_name(og_path: &str) -> (Option<NaiveDate>, String) {
+    let path = Path::new(og_path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let stem = match (stem, path.parent().and_then(|parent| parent.file_name())) {
+        ("index", Some(parent)) => parent.to_str().unwrap_or_default(),
+        _ => stem,
+    };
 
-    let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-        .map_err(|e| format!("Invalid date format: {}. Expected YYYY-MM-DD", e))?;
+    let date = stem
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+    match date {
+        Some(date) if stem.len() == 10 => (Some(date), String::new()),
+        Some(date) if stem[10..].starts_with('-') => (Some(date), stem[11..].to_string()),
+        _ => (None, stem.to_string()),
+    }
+}
 
-    let tags = tags_str
-        .split(',')
-        .map(|tag| tag.trim().to_string())
-        .collect::<Vec<String>>();
+/// Builds the metadata of the post at `og_path`. The date and slug come from
+/// the front matter when present, and from the file name otherwise.
+pub fn parse_metadata(og_path: &str, front_matter: FrontMatter) -> Result<Metadata, BuildError> {
+    build_metadata(og_path, front_matter).map_err(|message| BuildError::Metadata {
+        path: og_path.to_string(),
+        message,
+    })
+}
+
+fn build_metadata(og_path: &str, front_matter: FrontMatter) -> Result<Metadata, String> {
+    let mut front_matter = front_matter.clone();
+    let (file_name_date, file_name_slug) = parse_file_name(og_path);
+
+    let title = front_matter.remove("title").ok_or("Title is required")?;
+    let description = front_matter.remove("description");
+    let date = front_matter.remove("date");
+    let tags = front_matter.remove("tags").ok_or("Tags are required")?;
+    let slug = front_matter.remove("slug");
+    let draft = front_matter.remove("draft");
+    let expires = front_matter.remove("expires");
+
+    let title = value_to_string("Title", title)?;
+    // Posts without a description get their excerpt once rendered
+    let description = description
+        .map(|description| value_to_string("Description", description))
+        .transpose()?
+        .unwrap_or_default();
+    let tags = value_to_list("Tags", tags)?;
+
+    let date = match date {
+        Some(date) => value_to_date("Date", date)?,
+        None => file_name_date.ok_or("Date is required")?,
+    };
+    let draft = draft
+        .map(|draft| value_to_bool("Draft", draft))
+        .transpose()?
+        .unwrap_or_default();
+    let expires = expires
+        .map(|expires| value_to_date("Expires", expires))
+        .transpose()?;
+
+    let slug = match slug {
+        Some(slug) => parse_slug(slug)?,
+        None if !file_name_slug.is_empty() => slugify(&file_name_slug),
+        None => slugify(&title),
+    };
 
     Ok(Metadata {
         title,
         description,
         date,
+        slug,
         tags,
+        draft,
+        expires,
         extras: front_matter,
     })
 }
 
-pub fn parse_site_config<P: AsRef<Path>>(config: &P) -> Result<SiteConfig, String> {
-    let config =
-        fs::read_to_string(config).map_err(|e| format!("Failed to read site config: {}", e))?;
-    let config =
-        serde_yaml::from_str(&config).map_err(|e| format!("Invalid site config: {}", e))?;
+pub fn parse_site_config<P: AsRef<Path>>(config: &P) -> Result<SiteConfig, BuildError> {
+    let config = fs::read_to_string(config).map_err(|e| BuildError::Config {
+        message: format!("Failed to read site config: {}", e),
+    })?;
+    let config = serde_yaml::from_str(&config).map_err(|e| BuildError::Config {
+        message: format!("Invalid site config: {}", e),
+    })?;
     Ok(config)
 }
 
@@ -96,6 +351,13 @@ mod tests {
     use pretty_assertions::assert_eq;
     use textwrap::dedent;
 
+    fn string_front_matter(pairs: &[(&str, &str)]) -> FrontMatter {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+            .collect()
+    }
+
     #[test]
     fn test_parse_content_ok() {
         let content = dedent(
@@ -109,15 +371,14 @@ mod tests {
         );
         let content = content.trim();
 
-        let expected_front_matter = HashMap::from([
-            ("title".to_string(), "My First Post".to_string()),
-            ("date".to_string(), "2021-01-01".to_string()),
-        ]);
+        let expected_front_matter =
+            string_front_matter(&[("title", "My First Post"), ("date", "2021-01-01")]);
         let expected_body = "This is my first post.";
 
-        let post = parse_content("test.md", &content).unwrap();
+        let post = parse_content("test.md", content).unwrap();
         assert_eq!(post.front_matter, expected_front_matter);
         assert_eq!(post.body, expected_body);
+        assert_eq!(post.body_line, 5);
         assert_eq!(post.og_path, "test.md");
     }
 
@@ -132,12 +393,13 @@ mod tests {
         );
         let content = content.trim();
 
-        let expected_front_matter = HashMap::new();
+        let expected_front_matter = FrontMatter::new();
         let expected_body = "This is my first post.";
 
-        let post = parse_content("test.md", &content).unwrap();
+        let post = parse_content("test.md", content).unwrap();
         assert_eq!(post.front_matter, expected_front_matter);
         assert_eq!(post.body, expected_body);
+        assert_eq!(post.body_line, 3);
         assert_eq!(post.og_path, "test.md");
     }
 
@@ -153,13 +415,11 @@ mod tests {
         );
         let content = content.trim();
 
-        let expected_front_matter = HashMap::from([
-            ("title".to_string(), "My First Post".to_string()),
-            ("date".to_string(), "2021-01-01".to_string()),
-        ]);
+        let expected_front_matter =
+            string_front_matter(&[("title", "My First Post"), ("date", "2021-01-01")]);
         let expected_body = "";
 
-        let post = parse_content("test.md", &content).unwrap();
+        let post = parse_content("test.md", content).unwrap();
         assert_eq!(post.front_matter, expected_front_matter);
         assert_eq!(post.body, expected_body);
         assert_eq!(post.og_path, "test.md");
@@ -171,21 +431,24 @@ mod tests {
             "title: My First Post".to_string(),
             "date: 2021-01-01".to_string(),
         ];
-        let front_matter = parse_front_matter(front_matter).unwrap();
+        let front_matter = parse_front_matter("test.md", front_matter).unwrap();
         assert_eq!(
             front_matter.get("title"),
-            Some(&"My First Post".to_string())
+            Some(&Value::String("My First Post".to_string()))
+        );
+        assert_eq!(
+            front_matter.get("date"),
+            Some(&Value::String("2021-01-01".to_string()))
         );
-        assert_eq!(front_matter.get("date"), Some(&"2021-01-01".to_string()));
     }
 
     #[test]
     fn test_parse_front_matter_empty_field() {
         let front_matter = vec!["title: My First Post".to_string(), "date:".to_string()];
-        let front_matter = parse_front_matter(front_matter).unwrap();
+        let front_matter = parse_front_matter("test.md", front_matter).unwrap();
         assert_eq!(
             front_matter.get("title"),
-            Some(&"My First Post".to_string())
+            Some(&Value::String("My First Post".to_string()))
         );
         assert_eq!(front_matter.get("date"), None);
     }
@@ -193,23 +456,20 @@ mod tests {
     #[test]
     fn test_parse_front_matter_empty() {
         let front_matter = vec![];
-        let front_matter = parse_front_matter(front_matter).unwrap();
+        let front_matter = parse_front_matter("test.md", front_matter).unwrap();
         assert_eq!(front_matter.len(), 0);
     }
 
     #[test]
     fn test_parse_metadata_ok() {
-        let front_matter = HashMap::from([
-            ("title".to_string(), "My First Post".to_string()),
-            ("date".to_string(), "2021-01-01".to_string()),
-            (
-                "description".to_string(),
-                "This is my first post".to_string(),
-            ),
-            ("tags".to_string(), "tag1,tag2".to_string()),
+        let front_matter = string_front_matter(&[
+            ("title", "My First Post"),
+            ("date", "2021-01-01"),
+            ("description", "This is my first post"),
+            ("tags", "tag1,tag2"),
         ]);
 
-        let metadata = parse_metadata(front_matter).unwrap();
+        let metadata = parse_metadata("test.md", front_matter).unwrap();
         assert_eq!(metadata.title, "My First Post");
         assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
         assert_eq!(metadata.description, "This is my first post");
@@ -219,51 +479,507 @@ mod tests {
 
     #[test]
     fn test_parse_metadata_ok_extras() {
-        let front_matter = HashMap::from([
-            ("title".to_string(), "My First Post".to_string()),
-            ("date".to_string(), "2021-01-01".to_string()),
-            (
-                "description".to_string(),
-                "This is my first post".to_string(),
-            ),
-            ("tags".to_string(), "tag1,tag2".to_string()),
-            ("hello".to_string(), "world".to_string()),
-            ("foo".to_string(), "bar".to_string()),
+        let front_matter = string_front_matter(&[
+            ("title", "My First Post"),
+            ("date", "2021-01-01"),
+            ("description", "This is my first post"),
+            ("tags", "tag1,tag2"),
+            ("hello", "world"),
+            ("foo", "bar"),
         ]);
 
-        let metadata = parse_metadata(front_matter).unwrap();
+        let metadata = parse_metadata("test.md", front_matter).unwrap();
         assert_eq!(metadata.title, "My First Post");
         assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
         assert_eq!(metadata.description, "This is my first post");
         assert_eq!(metadata.tags, vec!["tag1", "tag2"]);
         assert_eq!(metadata.extras.len(), 2);
-        assert_eq!(metadata.extras.get("hello"), Some(&"world".to_string()));
-        assert_eq!(metadata.extras.get("foo"), Some(&"bar".to_string()));
+        assert_eq!(
+            metadata.extras.get("hello"),
+            Some(&Value::String("world".to_string()))
+        );
+        assert_eq!(
+            metadata.extras.get("foo"),
+            Some(&Value::String("bar".to_string()))
+        );
     }
 
     #[test]
     fn test_parse_metadata_invalid_date() {
-        let front_matter = HashMap::from([
-            ("title".to_string(), "My First Post".to_string()),
-            ("date".to_string(), "invalid-date".to_string()),
-            (
-                "description".to_string(),
-                "This is my first post".to_string(),
-            ),
-            ("tags".to_string(), "tag1,tag2".to_string()),
+        let front_matter = string_front_matter(&[
+            ("title", "My First Post"),
+            ("date", "invalid-date"),
+            ("description", "This is my first post"),
+            ("tags", "tag1,tag2"),
         ]);
 
-        let result = parse_metadata(front_matter);
+        let result = parse_metadata("test.md", front_matter);
         assert!(result.is_err());
-        assert!(result.err().unwrap().contains("Invalid date format"));
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .contains("Invalid date format")
+        );
     }
 
     #[test]
     fn test_parse_metadata_missing_required_fields() {
-        let front_matter = HashMap::from([("title".to_string(), "My First Post".to_string())]);
+        let front_matter = string_front_matter(&[("title", "My First Post")]);
 
-        let result = parse_metadata(front_matter);
+        let result = parse_metadata("test.md", front_matter);
         assert!(result.is_err());
-        assert_eq!(result.err().unwrap(), "Description is required");
+        assert_eq!(result.err().unwrap().message(), "Tags are required");
+    }
+
+    #[test]
+    fn test_parse_front_matter_typed_values() {
+        let front_matter = dedent(
+            "
+        title: My First Post
+        draft: false
+        weight: 3
+        tags: [rust, ssg]
+        authors:
+          - Ana
+          - Bob
+        summary: |
+          First line
+          Second line
+        social:
+          twitter: crustdown
+        ",
+        );
+        let front_matter = front_matter
+            .trim()
+            .lines()
+            .map(|line| line.to_string())
+            .collect();
+        let front_matter = parse_front_matter("test.md", front_matter).unwrap();
+
+        assert_eq!(front_matter.get("draft"), Some(&Value::Bool(false)));
+        assert_eq!(front_matter.get("weight"), Some(&Value::Number(3.into())));
+        assert_eq!(
+            front_matter.get("tags"),
+            Some(&Value::Sequence(vec![
+                Value::String("rust".to_string()),
+                Value::String("ssg".to_string()),
+            ]))
+        );
+        assert_eq!(
+            front_matter.get("authors"),
+            Some(&Value::Sequence(vec![
+                Value::String("Ana".to_string()),
+                Value::String("Bob".to_string()),
+            ]))
+        );
+        assert_eq!(
+            front_matter.get("summary"),
+            Some(&Value::String("First line\nSecond line\n".to_string()))
+        );
+        assert_eq!(
+            front_matter["social"]["twitter"],
+            Value::String("crustdown".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_front_matter_invalid_yaml() {
+        let front_matter = vec!["title: [unclosed".to_string()];
+        let result = parse_front_matter("test.md", front_matter);
+        assert!(result.is_err());
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .contains("Invalid front matter")
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_tags_list() {
+        let content = dedent(
+            "
+        ---
+        title: \"Quoted: title\"
+        date: 2021-01-01
+        description: This is my first post
+        tags:
+          - tag1
+          - tag2
+        weight: 10
+        ---
+        ",
+        );
+        let post = parse_content("test.md", content.trim()).unwrap();
+
+        let metadata = parse_metadata("test.md", post.front_matter).unwrap();
+        assert_eq!(metadata.title, "Quoted: title");
+        assert_eq!(metadata.tags, vec!["tag1", "tag2"]);
+        assert_eq!(
+            metadata.extras.get("weight"),
+            Some(&Value::Number(10.into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_non_scalar_title() {
+        let mut front_matter = string_front_matter(&[
+            ("date", "2021-01-01"),
+            ("description", "This is my first post"),
+            ("tags", "tag1,tag2"),
+        ]);
+        front_matter.insert("title".to_string(), Value::Sequence(vec![]));
+
+        let result = parse_metadata("test.md", front_matter);
+        assert_eq!(
+            result.err().unwrap().message(),
+            "Title must be a single value"
+        );
+    }
+
+    #[test]
+    fn test_parse_content_toml() {
+        let content = dedent(
+            "
+        +++
+        title = \"My First Post\"
+        date = 2021-01-01
+        tags = [\"tag1\", \"tag2\"]
+        draft = false
+        +++
+        This is my first post.
+        ",
+        );
+
+        let post = parse_content("test.md", content.trim()).unwrap();
+        assert_eq!(
+            post.front_matter.get("title"),
+            Some(&Value::String("My First Post".to_string()))
+        );
+        assert_eq!(
+            post.front_matter.get("date"),
+            Some(&Value::String("2021-01-01".to_string()))
+        );
+        assert_eq!(post.front_matter.get("draft"), Some(&Value::Bool(false)));
+        assert_eq!(post.body, "This is my first post.");
+
+        let mut front_matter = post.front_matter;
+        front_matter.insert("description".to_string(), "A post".into());
+        let metadata = parse_metadata("test.md", front_matter).unwrap();
+        assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
+        assert_eq!(metadata.tags, vec!["tag1", "tag2"]);
+    }
+
+    #[test]
+    fn test_parse_content_invalid_toml() {
+        let content = "+++\ntitle = \n+++\nThis is my first post.";
+        let result = parse_content("test.md", content);
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .contains("Invalid front matter")
+        );
+    }
+
+    #[test]
+    fn test_parse_content_json() {
+        let content = dedent(
+            r#"
+        {
+            "title": "My First Post",
+            "date": "2021-01-01",
+            "tags": ["tag1", "tag2"],
+            "weight": 2,
+            "subtitle": null
+        }
+        This is my first post.
+        {"not": "front matter"}
+        "#,
+        );
+
+        let post = parse_content("test.md", content.trim()).unwrap();
+        assert_eq!(
+            post.front_matter.get("title"),
+            Some(&Value::String("My First Post".to_string()))
+        );
+        assert_eq!(
+            post.front_matter.get("weight"),
+            Some(&Value::Number(2.into()))
+        );
+        assert_eq!(post.front_matter.get("subtitle"), None);
+        assert_eq!(
+            post.body,
+            "This is my first post.\n{\"not\": \"front matter\"}"
+        );
+        assert_eq!(post.body_line, 8);
+    }
+
+    #[test]
+    fn test_parse_content_invalid_json() {
+        let content = "{\"title\": \"My First Post\",\nThis is my first post.";
+        let result = parse_content("test.md", content);
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .contains("Invalid front matter")
+        );
+    }
+
+    #[test]
+    fn test_parse_content_keeps_dashes_in_body() {
+        let content = dedent(
+            "
+        ---
+        title: My First Post
+        ---
+        Setext heading
+        --------------
+
+        Some text
+
+        ---
+
+        ```yaml
+        ---
+        key: value
+        ---
+        ```
+        ",
+        );
+        let content = content.trim_start();
+
+        let expected_body = dedent(
+            "
+        Setext heading
+        --------------
+
+        Some text
+
+        ---
+
+        ```yaml
+        ---
+        key: value
+        ---
+        ```
+        ",
+        );
+
+        let post = parse_content("test.md", content).unwrap();
+        assert_eq!(
+            post.front_matter,
+            string_front_matter(&[("title", "My First Post")])
+        );
+        assert_eq!(post.body, expected_body.trim_start());
+    }
+
+    #[test]
+    fn test_parse_content_crlf() {
+        let content = "---\r\ntitle: My First Post\r\n---\r\nThis is my first post.\r\n";
+
+        let post = parse_content("test.md", content).unwrap();
+        assert_eq!(
+            post.front_matter,
+            string_front_matter(&[("title", "My First Post")])
+        );
+        assert_eq!(post.body, "This is my first post.\r\n");
+    }
+
+    #[test]
+    fn test_parse_content_unclosed_front_matter() {
+        let content = "---\ntitle: My First Post\nThis is my first post.";
+        let result = parse_content("test.md", content);
+        assert_eq!(
+            result.err().unwrap().message(),
+            "Front matter is not closed, expected a line with `---`"
+        );
+
+        let content = "+++\ntitle = \"My First Post\"\n";
+        let result = parse_content("test.md", content);
+        assert_eq!(
+            result.err().unwrap().message(),
+            "Front matter is not closed, expected a line with `+++`"
+        );
+    }
+
+    fn required_front_matter() -> FrontMatter {
+        string_front_matter(&[
+            ("title", "My First Post"),
+            ("description", "This is my first post"),
+            ("tags", "tag1,tag2"),
+        ])
+    }
+
+    #[test]
+    fn test_parse_file_name() {
+        assert_eq!(
+            parse_file_name("2025-05-04-welcome-to-jekyll.markdown"),
+            (
+                NaiveDate::from_ymd_opt(2025, 5, 4),
+                "welcome-to-jekyll".to_string()
+            )
+        );
+        assert_eq!(
+            parse_file_name("nested/2025-05-04.md"),
+            (NaiveDate::from_ymd_opt(2025, 5, 4), String::new())
+        );
+        assert_eq!(
+            parse_file_name("2025-05-04-my-trip/index.md"),
+            (NaiveDate::from_ymd_opt(2025, 5, 4), "my-trip".to_string())
+        );
+        assert_eq!(
+            parse_file_name("2025-13-04-not-a-date.md"),
+            (None, "2025-13-04-not-a-date".to_string())
+        );
+        assert_eq!(parse_file_name("about.md"), (None, "about".to_string()));
+    }
+
+    #[test]
+    fn test_parse_metadata_date_and_slug_from_file_name() {
+        let metadata = parse_metadata(
+            "posts/2025-05-04-Welcome to Jekyll.markdown",
+            required_front_matter(),
+        )
+        .unwrap();
+        assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2025, 5, 4).unwrap());
+        assert_eq!(metadata.slug, "welcome-to-jekyll");
+    }
+
+    #[test]
+    fn test_parse_metadata_front_matter_wins_over_file_name() {
+        let mut front_matter = required_front_matter();
+        front_matter.insert("date".to_string(), "2021-01-01".into());
+        front_matter.insert("slug".to_string(), "hello".into());
+
+        let metadata = parse_metadata("2025-05-04-welcome.md", front_matter).unwrap();
+        assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
+        assert_eq!(metadata.slug, "hello");
+        assert_eq!(metadata.extras.get("slug"), None);
+    }
+
+    #[test]
+    fn test_parse_metadata_invalid_slug() {
+        for slug in ["", "..", "../../x", "a/b", "a\\b"] {
+            let mut front_matter = required_front_matter();
+            front_matter.insert("date".to_string(), "2021-01-01".into());
+            front_matter.insert("slug".to_string(), slug.into());
+            let result = parse_metadata("test.md", front_matter);
+            assert_eq!(
+                result.err().unwrap().message(),
+                format!("Slug {:?} can't be used in a URL", slug)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_metadata_date_with_time() {
+        for date in [
+            "2025-05-04 10:00:00 +0200",
+            "2025-05-04 23:30:00 -05:00",
+            "2025-05-04T10:00:00Z",
+            "2025-05-04 10:00",
+            "2025-05-04 10:00:00.5",
+        ] {
+            let mut front_matter = required_front_matter();
+            front_matter.insert("date".to_string(), date.into());
+            let metadata = parse_metadata("test.md", front_matter).unwrap();
+            assert_eq!(metadata.date, NaiveDate::from_ymd_opt(2025, 5, 4).unwrap());
+        }
+
+        for date in ["2025-05-04x", "2025-05-04 noon", "2025-05-04 10:00 +2"] {
+            let mut front_matter = required_front_matter();
+            front_matter.insert("date".to_string(), date.into());
+            let result = parse_metadata("test.md", front_matter);
+            assert!(
+                result
+                    .err()
+                    .unwrap()
+                    .message()
+                    .starts_with("Invalid date format")
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_metadata_slug_from_title() {
+        let metadata = parse_metadata("2025-05-04.md", required_front_matter()).unwrap();
+        assert_eq!(metadata.slug, "my-first-post");
+    }
+
+    #[test]
+    fn test_parse_metadata_missing_date() {
+        let result = parse_metadata("about.md", required_front_matter());
+        assert_eq!(result.err().unwrap().message(), "Date is required");
+    }
+
+    #[test]
+    fn test_parse_metadata_draft_and_expires() {
+        let mut front_matter = required_front_matter();
+        front_matter.insert("date".to_string(), "2021-01-01".into());
+        let metadata = parse_metadata("test.md", front_matter.clone()).unwrap();
+        assert!(!metadata.draft);
+        assert_eq!(metadata.expires, None);
+
+        front_matter.insert("draft".to_string(), Value::Bool(true));
+        front_matter.insert("expires".to_string(), "2022-01-01".into());
+        let metadata = parse_metadata("test.md", front_matter.clone()).unwrap();
+        assert!(metadata.draft);
+        assert_eq!(metadata.expires, NaiveDate::from_ymd_opt(2022, 1, 1));
+        assert_eq!(metadata.extras.len(), 0);
+
+        front_matter.insert("draft".to_string(), "yes".into());
+        let result = parse_metadata("test.md", front_matter);
+        assert_eq!(
+            result.err().unwrap().message(),
+            "Draft must be true or false"
+        );
+    }
+
+    #[test]
+    fn test_parse_content_error_lines() {
+        let content = "---\ntitle: My First Post\ntags: a: b\n---\nBody";
+        let error = parse_content("posts/test.md", content).err().unwrap();
+        assert!(matches!(
+            error,
+            BuildError::FrontMatter { ref path, line: Some(3), .. } if path == "posts/test.md"
+        ));
+
+        let content = "+++\ntitle = \"My First Post\"\ndate = \n+++\nBody";
+        let error = parse_content("test.md", content).err().unwrap();
+        assert!(matches!(
+            error,
+            BuildError::FrontMatter { line: Some(3), .. }
+        ));
+
+        let content = "{\n  \"title\": \"My First Post\",\n  \"date\"\n}\nBody";
+        let error = parse_content("test.md", content).err().unwrap();
+        assert!(matches!(
+            error,
+            BuildError::FrontMatter { line: Some(4), .. }
+        ));
+
+        let error = parse_content("test.md", "Just a body").err().unwrap();
+        assert_eq!(error.to_string(), "test.md:1: No front matter found");
+    }
+
+    #[test]
+    fn test_parse_metadata_error_path() {
+        let error = parse_metadata("posts/test.md", FrontMatter::new())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            BuildError::Metadata {
+                path: "posts/test.md".to_string(),
+                message: "Title is required".to_string(),
+            }
+        );
     }
 }
@@ -1,16 +1,265 @@
-use std::collections::HashMap;
+use crate::errors::BuildError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::BTreeMap;
 use std::path::Path;
+use walkdir::{DirEntry, WalkDir};
 
-pub fn read_content<P: AsRef<Path>>(source_directory: &P) -> HashMap<String, String> {
-    let mut posts = HashMap::new();
+const CONTENT_EXTENSIONS: [&str; 2] = ["md", "markdown"];
 
-    let files = std::fs::read_dir(source_directory).unwrap();
-    for file in files {
-        let file_path = file.unwrap().path();
-        let file_name = file_path.file_name().unwrap().to_str().unwrap();
-        let file_content = std::fs::read_to_string(&file_path).unwrap();
-        posts.insert(file_name.to_string(), file_content);
+fn build_ignore_set(ignore: &[String]) -> Result<GlobSet, BuildError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in ignore {
+        let glob = Glob::new(pattern).map_err(|e| BuildError::Config {
+            message: format!("Invalid ignore pattern {}: {}", pattern, e),
+        })?;
+        builder.add(glob);
     }
+    builder.build().map_err(|e| BuildError::Config {
+        message: format!("Invalid ignore patterns: {}", e),
+    })
+}
+
+fn is_hidden_or_swap(entry: &DirEntry) -> bool {
+    let file_name = entry.file_name().to_string_lossy();
+    file_name.starts_with('.')
+        || file_name.ends_with('~')
+        || (file_name.starts_with('#') && file_name.ends_with('#'))
+        || [".swp", ".swo", ".swx"]
+            .iter()
+            .any(|extension| file_name.ends_with(extension))
+}
+
+fn is_content_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| CONTENT_EXTENSIONS.contains(&extension))
+}
+
+/// Path of the entry relative to the source directory, always using `/` as
+/// separator so routes look the same on every platform.
+fn relative_path(source_directory: &Path, entry: &DirEntry) -> String {
+    entry
+        .path()
+        .strip_prefix(source_directory)
+        .unwrap()
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Every file under `source_directory` except hidden files, editor swap
+/// files and anything matching one of the `ignore` globs.
+fn walk_source(source_directory: &Path, ignore: &[String]) -> Result<Vec<DirEntry>, BuildError> {
+    let ignore = build_ignore_set(ignore)?;
+
+    WalkDir::new(source_directory)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            !is_hidden_or_swap(entry) && !ignore.is_match(relative_path(source_directory, entry))
+        })
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .map_or(true, |entry| entry.file_type().is_file())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| BuildError::Read {
+            path: e.path().unwrap_or(source_directory).display().to_string(),
+            message: format!("Failed to read content: {}", e),
+        })
+}
+
+/// Reads every Markdown file under `source_directory`, keyed and ordered by
+/// its path relative to that directory. Hidden files, editor swap files and
+/// anything matching one of the `ignore` globs are skipped.
+pub fn read_content<P: AsRef<Path>>(
+    source_directory: &P,
+    ignore: &[String],
+) -> Result<BTreeMap<String, String>, BuildError> {
+    let source_directory = source_directory.as_ref();
+    let mut posts = BTreeMap::new();
+
+    for entry in walk_source(source_directory, ignore)? {
+        if !is_content_file(entry.path()) {
+            continue;
+        }
+
+        let file_content = std::fs::read_to_string(entry.path()).map_err(|e| BuildError::Read {
+            path: entry.path().display().to_string(),
+            message: format!("Failed to read content: {}", e),
+        })?;
+        posts.insert(relative_path(source_directory, &entry), file_content);
+    }
+
+    Ok(posts)
+}
+
+/// Paths, relative to `source_directory`, of the files sitting next to the
+/// posts that are not content themselves (images, downloads...).
+pub fn read_assets<P: AsRef<Path>>(
+    source_directory: &P,
+    ignore: &[String],
+) -> Result<Vec<String>, BuildError> {
+    let source_directory = source_directory.as_ref();
+
+    Ok(walk_source(source_directory, ignore)?
+        .iter()
+        .filter(|entry| !is_content_file(entry.path()))
+        .map(|entry| relative_path(source_directory, entry))
+        .collect())
+}
+
+/// Paths, relative to `source_directory`, of every file under it, hidden
+/// ones included. A missing `source_directory` has no files.
+pub fn read_files<P: AsRef<Path>>(source_directory: &P) -> Result<Vec<String>, BuildError> {
+    let source_directory = source_directory.as_ref();
+    if !source_directory.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in WalkDir::new(source_directory)
+        .min_depth(1)
+        .sort_by_file_name()
+    {
+        let entry = entry.map_err(|e| BuildError::Read {
+            path: source_directory.display().to_string(),
+            message: e.to_string(),
+        })?;
+        if entry.file_type().is_file() {
+            files.push(relative_path(source_directory, &entry));
+        }
+    }
+    Ok(files)
+}
 
-    posts
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_file(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn keys(posts: &BTreeMap<String, String>) -> Vec<&str> {
+        posts.keys().map(|key| key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_read_content_recursive() {
+        let temp_dir = TempDir::new().unwrap();
+        write_file(temp_dir.path(), "first.md", "first");
+        write_file(temp_dir.path(), "nested/second.markdown", "second");
+        write_file(temp_dir.path(), "nested/deeper/third.md", "third");
+
+        let posts = read_content(&temp_dir.path(), &[]).unwrap();
+        assert_eq!(
+            keys(&posts),
+            vec![
+                "first.md",
+                "nested/deeper/third.md",
+                "nested/second.markdown"
+            ]
+        );
+        assert_eq!(posts["nested/deeper/third.md"], "third");
+    }
+
+    #[test]
+    fn test_read_content_skips_hidden_and_swap_files() {
+        let temp_dir = TempDir::new().unwrap();
+        write_file(temp_dir.path(), "post.md", "post");
+        write_file(temp_dir.path(), ".hidden.md", "hidden");
+        write_file(temp_dir.path(), ".git/notes.md", "hidden dir");
+        write_file(temp_dir.path(), "post.md~", "backup");
+        write_file(temp_dir.path(), "post.md.swp", "vim");
+        write_file(temp_dir.path(), "#post.md#", "emacs");
+        write_file(temp_dir.path(), "image.png", "not markdown");
+
+        let posts = read_content(&temp_dir.path(), &[]).unwrap();
+        assert_eq!(keys(&posts), vec!["post.md"]);
+    }
+
+    #[test]
+    fn test_read_content_ignore_globs() {
+        let temp_dir = TempDir::new().unwrap();
+        write_file(temp_dir.path(), "post.md", "post");
+        write_file(temp_dir.path(), "README.md", "readme");
+        write_file(temp_dir.path(), "archive/old.md", "old");
+        write_file(temp_dir.path(), "notes/todo.md", "todo");
+
+        let ignore = vec![
+            "README.md".to_string(),
+            "archive".to_string(),
+            "**/todo.md".to_string(),
+        ];
+        let posts = read_content(&temp_dir.path(), &ignore).unwrap();
+        assert_eq!(keys(&posts), vec!["post.md"]);
+    }
+
+    #[test]
+    fn test_read_content_invalid_ignore_glob() {
+        let temp_dir = TempDir::new().unwrap();
+        let result = read_content(&temp_dir.path(), &["[".to_string()]);
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .message()
+                .contains("Invalid ignore pattern")
+        );
+    }
+
+    #[test]
+    fn test_read_assets() {
+        let temp_dir = TempDir::new().unwrap();
+        write_file(temp_dir.path(), "post.md", "post");
+        write_file(temp_dir.path(), "bundle/index.md", "bundle");
+        write_file(temp_dir.path(), "bundle/image.png", "png");
+        write_file(temp_dir.path(), "bundle/.DS_Store", "hidden");
+        write_file(temp_dir.path(), "drafts/sketch.png", "ignored");
+        write_file(temp_dir.path(), "slides.pdf", "pdf");
+
+        let mut assets = read_assets(&temp_dir.path(), &["drafts".to_string()]).unwrap();
+        assets.sort();
+        assert_eq!(assets, vec!["bundle/image.png", "slides.pdf"]);
+    }
+
+    #[test]
+    fn test_read_content_missing_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing");
+        let result = read_content(&missing, &[]);
+        assert!(matches!(
+            result.err().unwrap(),
+            BuildError::Read { path, .. } if path == missing.display().to_string()
+        ));
+    }
+
+    #[test]
+    fn test_read_files() {
+        let temp_dir = TempDir::new().unwrap();
+        write_file(temp_dir.path(), "favicon.ico", "icon");
+        write_file(temp_dir.path(), "css/vendor/pico.css", "css");
+        write_file(temp_dir.path(), ".well-known/security.txt", "contact");
+
+        assert_eq!(
+            read_files(&temp_dir.path()).unwrap(),
+            vec![
+                ".well-known/security.txt",
+                "css/vendor/pico.css",
+                "favicon.ico"
+            ]
+        );
+        assert!(
+            read_files(&temp_dir.path().join("static"))
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -1,75 +1,519 @@
-use crate::entities::{ContentKind, PostOutput, RawPost, SiteConfig};
-use minijinja::{Environment, context};
+use crate::content_excerpt::split_excerpt;
+use crate::content_feed::feeds_enabled;
+use crate::content_headings::{add_heading_ids, build_toc};
+use crate::content_highlight::Highlighter;
+use crate::content_liquid::{register_filters, translate_liquid};
+use crate::content_stats::post_stats;
+use crate::content_tags::TAGS_INDEX_PATH;
+use crate::entities::{
+    ContentKind, Excerpt, Feed, MarkdownConfig, Paginator, PostOutput, PostStats, RawPost,
+    ReadingConfig, SiteConfig, TagOutput, TocEntry,
+};
+use crate::errors::BuildError;
+use minijinja::{Environment, ErrorKind, Value, context, path_loader};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 
-fn render_markdown_post(post_body: &str) -> String {
-    markdown::to_html(post_body)
+const DEFAULT_LAYOUT: &str = "post";
+
+/// Templates of the other pages and the base they extend, which can't be used
+/// as the layout of a post
+const PAGE_TEMPLATES: [&str; 4] = ["index", "layout", "tag", "tags"];
+
+/// Templates bundled in the binary, used whenever the site doesn't provide
+/// its own version of them.
+const EMBEDDED_TEMPLATES: [(&str, &str); 8] = [
+    ("layout.html", include_str!("_templates/layout.html")),
+    ("post.html", include_str!("_templates/post.html")),
+    ("index.html", include_str!("_templates/index.html")),
+    ("feed.xml", include_str!("_templates/feed.xml")),
+    ("atom.xml", include_str!("_templates/atom.xml")),
+    ("tags.html", include_str!("_templates/tags.html")),
+    ("tag.html", include_str!("_templates/tag.html")),
+    (
+        "partials/pagination.html",
+        include_str!("_templates/partials/pagination.html"),
+    ),
+];
+
+fn embedded_template(name: &str) -> Option<&'static str> {
+    EMBEDDED_TEMPLATES
+        .iter()
+        .find(|(template_name, _)| *template_name == name)
+        .map(|(_, source)| *source)
+}
+
+/// Names of the templates pulled in by `extends`, `include`, `import` and
+/// `from` tags of a template source.
+fn referenced_templates(source: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for block in source.split("{%").skip(1) {
+        let tag = block.split("%}").next().unwrap_or_default();
+        let tag = tag.trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+        let keyword = tag.split_whitespace().next().unwrap_or_default();
+        if !["extends", "include", "import", "from"].contains(&keyword) {
+            continue;
+        }
+        // Every quoted string of the tag, an include can list several templates
+        for (index, part) in tag.split(['"', '\'']).enumerate() {
+            if index % 2 == 1 {
+                names.push(part.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Body of a post converted to HTML, with what was found along the way
+pub struct RenderedContent {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub excerpt: Excerpt,
+    pub stats: PostStats,
+}
+
+/// Body of a post once its Liquid tags were rendered
+pub struct LiquidBody {
+    pub body: String,
+    /// Tags and filters that couldn't be translated, with their line in the
+    /// file of the post
+    pub warnings: Vec<(usize, String)>,
+    /// Sources of the templates the body includes, keyed by name
+    pub templates: BTreeMap<String, String>,
+}
+
+/// Options of the markdown renderer for the constructs enabled in `config`
+fn markdown_options(config: &MarkdownConfig) -> markdown::Options {
+    let gfm = |construct: Option<bool>| construct.unwrap_or(config.gfm);
+    let base_constructs = if config.mdx {
+        markdown::Constructs::mdx()
+    } else {
+        markdown::Constructs::default()
+    };
+    markdown::Options {
+        parse: markdown::ParseOptions {
+            constructs: markdown::Constructs {
+                gfm_autolink_literal: gfm(config.autolinks),
+                gfm_footnote_definition: gfm(config.footnotes),
+                gfm_label_start_footnote: gfm(config.footnotes),
+                gfm_strikethrough: gfm(config.strikethrough),
+                gfm_table: gfm(config.tables),
+                gfm_task_list_item: gfm(config.task_lists),
+                math_flow: config.math,
+                math_text: config.math,
+                ..base_constructs
+            },
+            ..markdown::ParseOptions::default()
+        },
+        compile: markdown::CompileOptions {
+            allow_dangerous_html: config.raw_html,
+            gfm_tagfilter: config.gfm,
+            ..markdown::CompileOptions::default()
+        },
+    }
+}
+
+fn render_markdown_post(post_body: &str, options: &markdown::Options) -> Result<String, String> {
+    markdown::to_html_with_options(post_body, options).map_err(|e| e.to_string())
+}
+
+/// Layout configured in `_site.yml` for the deepest directory containing the
+/// post, falling back to `post`.
+fn default_layout<'a>(og_path: &str, site_config: &'a SiteConfig) -> &'a str {
+    let directory = Path::new(og_path)
+        .parent()
+        .and_then(|parent| parent.to_str())
+        .unwrap_or_default();
+
+    site_config
+        .layouts
+        .iter()
+        .filter(|(prefix, _)| {
+            let prefix = prefix.trim_matches('/');
+            prefix.is_empty()
+                || directory == prefix
+                || directory.starts_with(&format!("{}/", prefix))
+        })
+        .max_by_key(|(prefix, _)| prefix.trim_matches('/').len())
+        .map_or(DEFAULT_LAYOUT, |(_, layout)| layout.as_str())
 }
 
 pub struct Rendererer<'a> {
     env: Environment<'a>,
+    templates_dir: Option<PathBuf>,
+    highlighter: Option<Highlighter>,
 }
 
 impl Rendererer<'_> {
-    pub fn new() -> Self {
+    /// Templates in `templates_dir` take precedence over the embedded ones
+    /// with the same name, and can be pulled in from any other template
+    /// with `{% include %}` or `{% extends %}`.
+    pub fn new(templates_dir: Option<&Path>) -> Self {
         let mut env = Environment::new();
-        env.add_template("layout.html", include_str!("_templates/layout.html"))
-            .unwrap();
-        env.add_template("post.html", include_str!("_templates/post.html"))
-            .unwrap();
-        env.add_template("index.html", include_str!("_templates/index.html"))
-            .unwrap();
+        let site_loader =
+            templates_dir.map(|templates_dir| path_loader(templates_dir.to_path_buf()));
+        env.set_loader(move |name| {
+            if let Some(site_loader) = &site_loader
+                && let Some(source) = site_loader(name)?
+            {
+                return Ok(Some(source));
+            }
+            Ok(embedded_template(name).map(|source| source.to_string()))
+        });
+        register_filters(&mut env);
+
+        Rendererer {
+            env,
+            templates_dir: templates_dir.map(Path::to_path_buf),
+            highlighter: None,
+        }
+    }
+
+    /// Highlights the code blocks of the posts with `highlighter`
+    pub fn with_highlighter(mut self, highlighter: Option<Highlighter>) -> Self {
+        self.highlighter = highlighter;
+        self
+    }
+
+    /// Names of the templates a post can use as its `layout`, that is, every
+    /// top level `.html` template without the extension, but the page
+    /// templates.
+    pub fn available_layouts(&self) -> Vec<String> {
+        let mut layouts = EMBEDDED_TEMPLATES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<BTreeSet<_>>();
 
-        Rendererer { env }
+        if let Some(entries) = self
+            .templates_dir
+            .as_ref()
+            .and_then(|templates_dir| std::fs::read_dir(templates_dir).ok())
+        {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_file() && !file_name.starts_with('.') {
+                    layouts.insert(file_name);
+                }
+            }
+        }
+
+        layouts
+            .into_iter()
+            .filter(|name| !name.contains('/'))
+            .filter_map(|name| name.strip_suffix(".html").map(|name| name.to_string()))
+            .filter(|name| !PAGE_TEMPLATES.contains(&name.as_str()))
+            .collect()
+    }
+
+    /// Markdown constructs of the post, from the site configuration with the
+    /// ones set in the `markdown` section of its front matter replaced
+    pub fn markdown_config(
+        &self,
+        post: &PostOutput,
+        site_config: &SiteConfig,
+    ) -> Result<MarkdownConfig, BuildError> {
+        let Some(overrides) = post.metadata.extras.get("markdown") else {
+            return Ok(site_config.markdown.clone());
+        };
+        let config_error = |message: String| BuildError::Render {
+            path: post.source_path.clone(),
+            message: format!("Invalid markdown options: {}", message),
+        };
+        let serde_yaml::Value::Mapping(overrides) = overrides else {
+            return Err(config_error("expected a mapping".to_string()));
+        };
+        let mut config = match serde_yaml::to_value(&site_config.markdown) {
+            Ok(serde_yaml::Value::Mapping(config)) => config,
+            _ => serde_yaml::Mapping::new(),
+        };
+        config.extend(overrides.clone());
+        serde_yaml::from_value(serde_yaml::Value::Mapping(config))
+            .map_err(|e| config_error(e.to_string()))
+    }
+
+    /// Converts the body of the post to HTML according to its kind, with the
+    /// markdown constructs of `markdown_config`, along with the table of
+    /// contents of its headings, its excerpt and its stats
+    pub fn render_content(
+        &self,
+        content: &RawPost,
+        markdown_config: &MarkdownConfig,
+        reading_config: &ReadingConfig,
+    ) -> Result<RenderedContent, BuildError> {
+        match content.kind {
+            ContentKind::Markdown => {
+                let options = markdown_options(markdown_config);
+                let html = render_markdown_post(&content.body, &options).map_err(|message| {
+                    BuildError::Render {
+                        path: content.og_path.clone(),
+                        message: format!("Invalid markdown: {}", message),
+                    }
+                })?;
+                let html = match &self.highlighter {
+                    Some(highlighter) => {
+                        highlighter.highlight_code_blocks(&html, &content.body, &options.parse)
+                    }
+                    None => html,
+                };
+                let (html, headings) = add_heading_ids(&html, markdown_config.heading_anchors);
+                let (html, excerpt) = split_excerpt(&html);
+                Ok(RenderedContent {
+                    html,
+                    toc: build_toc(headings),
+                    excerpt,
+                    stats: post_stats(&content.body, &options.parse, reading_config),
+                })
+            }
+        }
+    }
+
+    /// Whether the Liquid tags of the post are translated, from its front
+    /// matter or the site configuration
+    pub fn uses_liquid(&self, post: &PostOutput, site_config: &SiteConfig) -> bool {
+        post.metadata
+            .extras
+            .get("liquid")
+            .and_then(|liquid| liquid.as_bool())
+            .unwrap_or(site_config.liquid)
+    }
+
+    /// Translates the Jekyll Liquid of the body of `content` and renders it,
+    /// with the front matter and url of the post as `page` like in Jekyll.
+    pub fn render_liquid(
+        &self,
+        content: &RawPost,
+        post: &PostOutput,
+        site_config: &SiteConfig,
+    ) -> Result<LiquidBody, BuildError> {
+        let (source, warnings) = translate_liquid(&content.body);
+        let warnings = warnings
+            .into_iter()
+            .map(|(line, message)| (content.body_line + line - 1, message))
+            .collect();
+        let body = self
+            .env
+            .render_str(
+                &source,
+                context! {
+                    page => context! {
+                        url => post.url,
+                        path => post.source_path,
+                        ..Value::from_serialize(&post.metadata)
+                    },
+                    post => post,
+                    site => site_config,
+                },
+            )
+            .map_err(|e| BuildError::Render {
+                path: post.source_path.clone(),
+                message: format!("Failed to render Liquid: {}", e),
+            })?;
+
+        let mut templates = BTreeMap::new();
+        for name in referenced_templates(&source) {
+            templates.extend(self.template_dependencies(&name));
+        }
+        Ok(LiquidBody {
+            body,
+            warnings,
+            templates,
+        })
     }
 
-    pub fn render_post(&self, content: &RawPost, site_config: &SiteConfig) -> String {
-        let body = match content.kind {
-            ContentKind::Markdown => render_markdown_post(&content.body),
+    /// Layout of the post, from its front matter or the site configuration
+    pub fn post_layout<'a>(
+        &self,
+        post: &'a PostOutput,
+        site_config: &'a SiteConfig,
+    ) -> Result<&'a str, BuildError> {
+        let layout = match post.metadata.extras.get("layout") {
+            Some(layout) => layout.as_str().ok_or_else(|| BuildError::Render {
+                path: post.source_path.clone(),
+                message: "Layout must be a string".to_string(),
+            })?,
+            None => default_layout(&post.source_path, site_config),
         };
+        if PAGE_TEMPLATES.contains(&layout) {
+            return Err(BuildError::Render {
+                path: post.source_path.clone(),
+                message: format!(
+                    "Layout {} is the template of another page, available layouts are: {}",
+                    layout,
+                    self.available_layouts().join(", ")
+                ),
+            });
+        }
+        Ok(layout)
+    }
 
-        let metadata = content.front_matter.clone();
+    /// Sources of `template_name` and of every template it extends, includes
+    /// or imports, directly or not, keyed by name. Only templates referenced
+    /// by a literal name can be followed, and missing ones are left out.
+    pub fn template_dependencies(&self, template_name: &str) -> BTreeMap<String, String> {
+        let mut dependencies = BTreeMap::new();
+        let mut pending = vec![template_name.to_string()];
+        while let Some(name) = pending.pop() {
+            if dependencies.contains_key(&name) {
+                continue;
+            }
+            let Ok(template) = self.env.get_template(&name) else {
+                continue;
+            };
+            pending.extend(referenced_templates(template.source()));
+            dependencies.insert(name, template.source().to_string());
+        }
+        dependencies
+    }
 
-        let template = self.env.get_template("post.html").unwrap();
+    /// Renders the whole page of a post with its layout
+    pub fn render_post(
+        &self,
+        post: &PostOutput,
+        site_config: &SiteConfig,
+    ) -> Result<String, BuildError> {
+        let render_error = |message: String| BuildError::Render {
+            path: post.source_path.clone(),
+            message,
+        };
+        let layout = self.post_layout(post, site_config)?;
+
+        let template = self
+            .env
+            .get_template(&format!("{}.html", layout))
+            .map_err(|e| match e.kind() {
+                ErrorKind::TemplateNotFound => render_error(format!(
+                    "Layout {} does not exist, available layouts are: {}",
+                    layout,
+                    self.available_layouts().join(", ")
+                )),
+                _ => render_error(format!("Invalid layout {}: {}", layout, e)),
+            })?;
         template
             .render(context! {
-                post => context! {
-                    content => body,
-                    metadata => metadata
-                },
+                post => post,
                 site => site_config,
+                feeds_enabled => feeds_enabled(site_config),
             })
-            .unwrap()
+            .map_err(|e| render_error(format!("Failed to render: {}", e)))
     }
 
-    pub fn render_index(&self, posts: &Vec<PostOutput>, site_config: &SiteConfig) -> String {
-        let template = self.env.get_template("index.html").unwrap();
+    /// Renders `template_name` for the page at `path`, reporting any failure
+    /// against that path
+    fn render_page<S: Serialize>(
+        &self,
+        template_name: &str,
+        path: &str,
+        ctx: S,
+    ) -> Result<String, BuildError> {
+        let render_error = |message: String| BuildError::Render {
+            path: path.to_string(),
+            message,
+        };
+        let template = self
+            .env
+            .get_template(template_name)
+            .map_err(|e| render_error(format!("Invalid template {}: {}", template_name, e)))?;
         template
-            .render(context! {
+            .render(ctx)
+            .map_err(|e| render_error(format!("Failed to render: {}", e)))
+    }
+
+    /// Renders a page of the index, `posts` being all of them and the
+    /// `paginator` holding the ones of this page
+    pub fn render_index(
+        &self,
+        posts: &[PostOutput],
+        paginator: &Paginator<PostOutput>,
+        site_config: &SiteConfig,
+    ) -> Result<String, BuildError> {
+        self.render_page(
+            "index.html",
+            &paginator.route,
+            context! {
                 posts => posts,
+                paginator => paginator,
                 site => site_config,
-            })
-            .unwrap()
+                feeds_enabled => feeds_enabled(site_config),
+            },
+        )
+    }
+
+    /// Renders the page listing every tag with its number of posts
+    pub fn render_tags(
+        &self,
+        tags: &[TagOutput],
+        site_config: &SiteConfig,
+    ) -> Result<String, BuildError> {
+        self.render_page(
+            "tags.html",
+            TAGS_INDEX_PATH,
+            context! {
+                tags => tags,
+                site => site_config,
+                feeds_enabled => feeds_enabled(site_config),
+            },
+        )
+    }
+
+    /// Renders a page of the listing of the posts with a given tag
+    pub fn render_tag(
+        &self,
+        tag: &TagOutput,
+        paginator: &Paginator<PostOutput>,
+        site_config: &SiteConfig,
+    ) -> Result<String, BuildError> {
+        self.render_page(
+            "tag.html",
+            &paginator.route,
+            context! {
+                tag => tag,
+                paginator => paginator,
+                site => site_config,
+                feeds_enabled => feeds_enabled(site_config),
+            },
+        )
+    }
+
+    /// Renders one of the feed templates (`feed.xml` or `atom.xml`)
+    pub fn render_feed(
+        &self,
+        template_name: &str,
+        feed: &Feed,
+        site_config: &SiteConfig,
+    ) -> Result<String, BuildError> {
+        self.render_page(
+            template_name,
+            template_name,
+            context! {
+                feed => feed,
+                site => site_config,
+            },
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::content_paginator::paginate;
+    use crate::entities::{FeedConfig, FeedItem, FrontMatter, Metadata};
+    use chrono::NaiveDate;
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
     use textwrap::dedent;
 
     #[test]
     fn test_render_markdown_post() {
         let post_body = "Hello, world!";
-        let rendered_post = render_markdown_post(post_body);
+        let rendered_post = render_markdown_post(post_body, &markdown::Options::default()).unwrap();
         assert_eq!(rendered_post, "<p>Hello, world!</p>");
     }
 
     #[test]
     fn test_render_markdown_post_with_title() {
         let post_body = "# Test\nHello, world!";
-        let rendered_post = render_markdown_post(post_body);
+        let rendered_post = render_markdown_post(post_body, &markdown::Options::default()).unwrap();
         assert_eq!(rendered_post, "<h1>Test</h1>\n<p>Hello, world!</p>");
     }
 
@@ -95,26 +539,37 @@ mod tests {
         );
         let expected_post_body = expected_post_body.trim();
 
-        let rendered_post = render_markdown_post(post_body);
+        let rendered_post = render_markdown_post(post_body, &markdown::Options::default()).unwrap();
         assert_eq!(rendered_post, expected_post_body);
     }
 
+    fn post(source_path: &str, title: &str, extras: FrontMatter) -> PostOutput {
+        PostOutput {
+            route: "test/index.html".to_string(),
+            url: "/test/".to_string(),
+            source_path: source_path.to_string(),
+            metadata: Metadata {
+                title: title.to_string(),
+                date: NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                slug: "test".to_string(),
+                extras,
+                ..Default::default()
+            },
+            content: "<p>Hello, world!</p>".to_string(),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_render_post() {
-        let renderer = Rendererer::new();
-        let mut metadata = HashMap::new();
-        metadata.insert("title".to_string(), "¡Hola mundo!".to_string());
-        let post = RawPost {
-            og_path: "test.md".to_string(),
-            front_matter: metadata,
-            body: "Hello, world!".to_string(),
-            kind: ContentKind::Markdown,
-        };
+        let renderer = Rendererer::new(None);
+        let post = post("test.md", "¡Hola mundo!", FrontMatter::new());
         let site_config = SiteConfig {
             title: "Blog".to_string(),
             description: "A blog about my life".to_string(),
+            ..Default::default()
         };
-        let rendered_post = renderer.render_post(&post, &site_config);
+        let rendered_post = renderer.render_post(&post, &site_config).unwrap();
         assert_eq!(
             rendered_post,
             "<!DOCTYPE html>
@@ -134,7 +589,7 @@ mod tests {
     <article>
         <header>
             <h2>¡Hola mundo!</h2>
-            <small></small>
+            <small>2021-01-01</small>
         </header>
         <section>
 <!-- Post content -->
@@ -148,4 +603,471 @@ mod tests {
 </html>"
         );
     }
+
+    #[test]
+    fn test_render_index_with_templates_dir() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("index.html"),
+            "{% extends \"layout.html\" %}{% block content %}{% include \"partials/count.html\" %}{% endblock %}",
+        )
+        .unwrap();
+        std::fs::create_dir(templates_dir.path().join("partials")).unwrap();
+        std::fs::write(
+            templates_dir.path().join("partials/count.html"),
+            "{{ posts | length }} posts",
+        )
+        .unwrap();
+        std::fs::write(
+            templates_dir.path().join("layout.html"),
+            "<h1>{{ site.title }}</h1>{% block content %}{% endblock %}",
+        )
+        .unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let site_config = SiteConfig {
+            title: "Blog".to_string(),
+            ..Default::default()
+        };
+        let paginator = paginate(&[], None, "index.html").remove(0);
+        let rendered_index = renderer
+            .render_index(&[], &paginator, &site_config)
+            .unwrap();
+        assert_eq!(rendered_index, "<h1>Blog</h1>0 posts");
+    }
+
+    #[test]
+    fn test_render_post_falls_back_to_embedded_templates() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("layout.html"),
+            "<main>{% block content %}{% endblock %}</main>",
+        )
+        .unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let post = post("test.md", "Hola", FrontMatter::new());
+        let rendered_post = renderer.render_post(&post, &SiteConfig::default()).unwrap();
+        assert!(rendered_post.starts_with("<main>\n    <article>"));
+        assert!(rendered_post.contains("<h2>Hola</h2>"));
+        assert!(rendered_post.ends_with("</article>\n</main>"));
+    }
+
+    #[test]
+    fn test_render_post_with_layout() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("talk.html"),
+            "Talk: {{ post.metadata.title }} at {{ post.url }} from {{ post.source_path }} ({{ post.metadata.layout }})",
+        )
+        .unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let post = post(
+            "talk.md",
+            "Hola",
+            FrontMatter::from([("layout".to_string(), "talk".into())]),
+        );
+        let rendered_post = renderer.render_post(&post, &SiteConfig::default()).unwrap();
+        assert_eq!(
+            rendered_post,
+            "Talk: Hola at &#x2f;test&#x2f; from talk.md (talk)"
+        );
+    }
+
+    #[test]
+    fn test_metadata_order() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("fields.html"),
+            "{% for field in post.metadata %}{{ field }} {% endfor %}",
+        )
+        .unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let extras = ["zebra", "layout", "apple", "mango", "kiwi", "banana"]
+            .map(|field| (field.to_string(), "fields".into()));
+        let post = post("fields.md", "Fields", FrontMatter::from(extras));
+        let rendered_post = renderer.render_post(&post, &SiteConfig::default()).unwrap();
+        assert_eq!(
+            rendered_post,
+            "apple banana date description draft expires kiwi layout mango slug tags title zebra "
+        );
+    }
+
+    #[test]
+    fn test_render_post_with_missing_layout() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(templates_dir.path().join("talk.html"), "").unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let slides = post(
+            "slides.md",
+            "Slides",
+            FrontMatter::from([("layout".to_string(), "slides".into())]),
+        );
+        let result = renderer.render_post(&slides, &SiteConfig::default());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "slides.md: Layout slides does not exist, available layouts are: post, talk"
+        );
+
+        let home = post(
+            "home.md",
+            "Home",
+            FrontMatter::from([("layout".to_string(), "index".into())]),
+        );
+        let result = renderer.render_post(&home, &SiteConfig::default());
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "home.md: Layout index is the template of another page, available layouts are: post, talk"
+        );
+    }
+
+    #[test]
+    fn test_default_layout_per_directory() {
+        let site_config = SiteConfig {
+            layouts: BTreeMap::from([
+                ("".to_string(), "article".to_string()),
+                ("talks".to_string(), "talk".to_string()),
+                ("talks/keynotes".to_string(), "keynote".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(default_layout("post.md", &site_config), "article");
+        assert_eq!(default_layout("talks/intro.md", &site_config), "talk");
+        assert_eq!(
+            default_layout("talks/keynotes/rustconf.md", &site_config),
+            "keynote"
+        );
+        assert_eq!(
+            default_layout("talksabout/post.md", &site_config),
+            "article"
+        );
+        assert_eq!(default_layout("post.md", &SiteConfig::default()), "post");
+    }
+
+    #[test]
+    fn test_render_feeds() {
+        let renderer = Rendererer::new(None);
+        let site_config = SiteConfig {
+            title: "Fish & Chips".to_string(),
+            description: "A blog".to_string(),
+            ..Default::default()
+        };
+        let feed = Feed {
+            site_url: "https://example.com/".to_string(),
+            rss_url: "https://example.com/feed.xml".to_string(),
+            atom_url: "https://example.com/atom.xml".to_string(),
+            updated: "2021-01-01T00:00:00+00:00".to_string(),
+            updated_rfc2822: "Fri, 1 Jan 2021 00:00:00 +0000".to_string(),
+            full_content: true,
+            items: vec![FeedItem {
+                title: "Hello".to_string(),
+                link: "https://example.com/posts/hello/index.html".to_string(),
+                summary: "Hi".to_string(),
+                content: "<p>Hello</p>".to_string(),
+                published: "2021-01-01T00:00:00+00:00".to_string(),
+                published_rfc2822: "Fri, 1 Jan 2021 00:00:00 +0000".to_string(),
+                tags: vec!["rust".to_string()],
+                stats: PostStats::default(),
+            }],
+        };
+
+        let rss = renderer
+            .render_feed("feed.xml", &feed, &site_config)
+            .unwrap();
+        assert!(
+            rss.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\"")
+        );
+        assert!(rss.contains("<title>Fish &amp; Chips</title>"));
+        assert!(rss.contains("<pubDate>Fri, 1 Jan 2021 00:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<category>rust</category>"));
+        assert!(rss.contains("<description>&lt;p&gt;Hello&lt;&#x2f;p&gt;</description>"));
+
+        let atom = renderer
+            .render_feed("atom.xml", &feed, &site_config)
+            .unwrap();
+        assert!(atom.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+        assert!(atom.contains("<updated>2021-01-01T00:00:00+00:00</updated>"));
+        assert!(atom.contains("<summary>Hi</summary>"));
+        assert!(atom.contains("<content type=\"html\">&lt;p&gt;Hello&lt;&#x2f;p&gt;</content>"));
+    }
+
+    #[test]
+    fn test_feed_links() {
+        let renderer = Rendererer::new(None);
+        let post = post("test.md", "Hola", FrontMatter::new());
+        let mut site_config = SiteConfig {
+            base_url: Some("https://example.com/".to_string()),
+            ..Default::default()
+        };
+        let rendered_post = renderer.render_post(&post, &site_config).unwrap();
+        assert!(rendered_post.contains("type=\"application/rss+xml\""));
+        assert!(rendered_post.contains("type=\"application/atom+xml\""));
+
+        site_config.feed = Some(FeedConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        let rendered_post = renderer.render_post(&post, &site_config).unwrap();
+        assert!(!rendered_post.contains("rel=\"alternate\""));
+    }
+
+    #[test]
+    fn test_render_index_template_error() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("index.html"),
+            "{% for post in posts %}",
+        )
+        .unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let error = renderer
+            .render_index(
+                &[],
+                &paginate(&[], None, "index.html").remove(0),
+                &SiteConfig::default(),
+            )
+            .err()
+            .unwrap();
+        assert!(matches!(error, BuildError::Render { ref path, .. } if path == "index.html"));
+        assert!(error.message().starts_with("Invalid template index.html"));
+    }
+
+    #[test]
+    fn test_referenced_templates() {
+        let source = r#"{% extends "layout.html" %}
+{%- include ['sidebar.html', "footer.html"] -%}
+{% from "macros.html" import card %}
+{% if include %}"quoted"{% endif %}"#;
+        assert_eq!(
+            referenced_templates(source),
+            vec!["layout.html", "sidebar.html", "footer.html", "macros.html"]
+        );
+    }
+
+    #[test]
+    fn test_template_dependencies() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("post.html"),
+            r#"{% extends "layout.html" %}{% block content %}{% include "share.html" %}{% endblock %}"#,
+        )
+        .unwrap();
+        std::fs::write(templates_dir.path().join("share.html"), "Share").unwrap();
+
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let dependencies = renderer.template_dependencies("post.html");
+        assert_eq!(
+            dependencies.keys().collect::<Vec<_>>(),
+            vec!["layout.html", "post.html", "share.html"]
+        );
+        assert_eq!(dependencies["share.html"], "Share");
+    }
+
+    #[test]
+    fn test_render_liquid() {
+        let templates_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            templates_dir.path().join("note.html"),
+            "<aside>{{ include.text }}</aside>",
+        )
+        .unwrap();
+        let renderer = Rendererer::new(Some(templates_dir.path()));
+        let site_config = SiteConfig {
+            title: "Blog".to_string(),
+            ..Default::default()
+        };
+        let mut extras = FrontMatter::new();
+        extras.insert("liquid".to_string(), serde_yaml::Value::Bool(true));
+        let post = post("imported.md", "Imported", extras);
+        let content = RawPost {
+            front_matter: FrontMatter::new(),
+            body: "{{ page.title }} on {{ site.title | upcase }} at {{ page.url }}\n\
+                   {% include note.html text=\"Hi\" %} {% post_url old %}"
+                .to_string(),
+            og_path: "imported.md".to_string(),
+            body_line: 1,
+            kind: ContentKind::Markdown,
+        };
+
+        assert!(renderer.uses_liquid(&post, &site_config));
+        let liquid = renderer
+            .render_liquid(&content, &post, &site_config)
+            .unwrap();
+        assert_eq!(
+            liquid.body,
+            "Imported on BLOG at /test/\n<aside>Hi</aside> {% post_url old %}"
+        );
+        assert_eq!(
+            liquid.warnings,
+            vec![(2, "Unsupported Liquid tag post_url".to_string())]
+        );
+        assert_eq!(
+            liquid.templates.keys().collect::<Vec<_>>(),
+            vec!["note.html"]
+        );
+
+        let post = self::post("native.md", "Native", FrontMatter::new());
+        assert!(!renderer.uses_liquid(&post, &site_config));
+    }
+
+    #[test]
+    fn test_render_content_with_markdown_options() {
+        let renderer = Rendererer::new(None);
+        let content = RawPost {
+            front_matter: FrontMatter::new(),
+            body: "| a |\n| - |\n| ~~b~~ |\n\n<em>raw</em> https://example.com".to_string(),
+            og_path: "gfm.md".to_string(),
+            body_line: 1,
+            kind: ContentKind::Markdown,
+        };
+
+        let commonmark = renderer
+            .render_content(
+                &content,
+                &MarkdownConfig::default(),
+                &ReadingConfig::default(),
+            )
+            .unwrap()
+            .html;
+        assert_eq!(
+            commonmark,
+            "<p>| a |\n| - |\n| ~~b~~ |</p>\n<p>&lt;em&gt;raw&lt;/em&gt; https://example.com</p>"
+        );
+
+        let gfm = MarkdownConfig {
+            gfm: true,
+            strikethrough: Some(false),
+            raw_html: true,
+            ..Default::default()
+        };
+        let html = renderer
+            .render_content(&content, &gfm, &ReadingConfig::default())
+            .unwrap()
+            .html;
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<td>~~b~~</td>"));
+        assert!(html.contains("<em>raw</em> <a href=\"https://example.com\">"));
+    }
+
+    #[test]
+    fn test_markdown_config_per_post() {
+        let renderer = Rendererer::new(None);
+        let site_config = SiteConfig {
+            markdown: MarkdownConfig {
+                gfm: true,
+                math: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let post = post("site.md", "Site", FrontMatter::new());
+        assert_eq!(
+            renderer.markdown_config(&post, &site_config).unwrap(),
+            site_config.markdown
+        );
+
+        let mut extras = FrontMatter::new();
+        extras.insert(
+            "markdown".to_string(),
+            serde_yaml::from_str("{math: false, tables: false}").unwrap(),
+        );
+        let post = self::post("override.md", "Override", extras);
+        assert_eq!(
+            renderer.markdown_config(&post, &site_config).unwrap(),
+            MarkdownConfig {
+                gfm: true,
+                tables: Some(false),
+                ..Default::default()
+            }
+        );
+
+        let mut extras = FrontMatter::new();
+        extras.insert(
+            "markdown".to_string(),
+            serde_yaml::Value::String("gfm".to_string()),
+        );
+        let post = self::post("invalid.md", "Invalid", extras);
+        assert_eq!(
+            renderer
+                .markdown_config(&post, &site_config)
+                .unwrap_err()
+                .to_string(),
+            "invalid.md: Invalid markdown options: expected a mapping"
+        );
+    }
+
+    #[test]
+    fn test_render_post_with_toc() {
+        let renderer = Rendererer::new(None);
+        let content = RawPost {
+            front_matter: FrontMatter::new(),
+            body: "## Setup\n\n### Install\n\n## Usage".to_string(),
+            og_path: "toc.md".to_string(),
+            body_line: 1,
+            kind: ContentKind::Markdown,
+        };
+        let markdown_config = MarkdownConfig {
+            heading_anchors: true,
+            ..Default::default()
+        };
+        let rendered = renderer
+            .render_content(&content, &markdown_config, &ReadingConfig::default())
+            .unwrap();
+        assert!(
+            rendered
+                .html
+                .starts_with("<h2 id=\"setup\">Setup <a class=\"heading-anchor\" href=\"#setup\"")
+        );
+
+        let mut post = post("toc.md", "Toc", FrontMatter::new());
+        post.content = rendered.html;
+        post.toc = rendered.toc;
+        let rendered_post = renderer.render_post(&post, &SiteConfig::default()).unwrap();
+        assert!(rendered_post.contains(
+            "<nav class=\"toc\">
+            <ul>
+                <li><a href=\"#setup\">Setup</a><ul>
+                <li><a href=\"#install\">Install</a></li></ul></li>
+                <li><a href=\"#usage\">Usage</a></li>
+            </ul>
+        </nav>"
+        ));
+    }
+
+    #[test]
+    fn test_render_content_excerpt() {
+        let renderer = Rendererer::new(None);
+        let content = RawPost {
+            front_matter: FrontMatter::new(),
+            body: "Read [the docs][docs] first.\n\n<!--more-->\n\nThe rest.\n\n[docs]: /docs/"
+                .to_string(),
+            og_path: "excerpt.md".to_string(),
+            body_line: 1,
+            kind: ContentKind::Markdown,
+        };
+
+        for raw_html in [false, true] {
+            let markdown_config = MarkdownConfig {
+                raw_html,
+                ..Default::default()
+            };
+            let rendered = renderer
+                .render_content(&content, &markdown_config, &ReadingConfig::default())
+                .unwrap();
+            assert_eq!(
+                rendered.html,
+                "<p>Read <a href=\"/docs/\">the docs</a> first.</p>\n<p>The rest.</p>\n"
+            );
+            assert_eq!(
+                rendered.excerpt.html,
+                "<p>Read <a href=\"/docs/\">the docs</a> first.</p>"
+            );
+            assert_eq!(rendered.excerpt.text, "Read the docs first.");
+        }
+    }
 }
@@ -1,12 +1,83 @@
-use crate::entities::{ContentKind, RawPost};
+use crate::entities::{ContentKind, Metadata, RawPost};
+use crate::errors::BuildError;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Lowercase, hyphenated version of `text` safe to use in URLs. Text without
+/// any ASCII letter or digit keeps its other letters and digits, with
+/// whitespace hyphenated, and is empty when it has none.
+pub fn slugify(text: &str) -> String {
+    let slug = sluggify::sluggify::sluggify(text, None);
+    if !slug.is_empty() {
+        return slug;
+    }
+    text.split(|c: char| c.is_whitespace() || c == '-')
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// Root relative URL of a route, `index.html` pages being linked through
+/// their directory.
+pub fn route_url(route: &str) -> String {
+    let route = route.trim_start_matches('/');
+    let path = match route.strip_suffix("index.html") {
+        Some(directory) if directory.is_empty() || directory.ends_with('/') => directory,
+        _ => route,
+    };
+    format!("/{}", path)
+}
+
+/// Keeps the URLs crustdown has always produced: `posts/<path>/index.html`
+pub const DEFAULT_PERMALINK: &str = "/posts/:path/";
+
+/// Categories as Jekyll reads them: either a list or a space separated string
+fn value_to_segments(value: &serde_yaml::Value) -> Vec<String> {
+    match value {
+        serde_yaml::Value::Sequence(values) => values
+            .iter()
+            .filter_map(|value| match value {
+                serde_yaml::Value::String(value) => Some(slugify(value)),
+                serde_yaml::Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            })
+            .collect(),
+        serde_yaml::Value::String(value) => value.split_whitespace().map(slugify).collect(),
+        serde_yaml::Value::Number(value) => vec![value.to_string()],
+        _ => vec![],
+    }
+}
 
 pub struct ContentRouter {
-    posts_path: String,
+    permalink: String,
 }
 
 impl ContentRouter {
-    pub fn new(posts_path: String) -> Self {
-        Self { posts_path }
+    /// `permalink` is a pattern such as `/:year/:month/:day/:slug/`. Patterns
+    /// ending with `/` produce an `index.html` in that directory, patterns
+    /// ending with a file name such as `/:slug.html` are used as they are.
+    ///
+    /// Jekyll's built-in styles `pretty`, `date`, `ordinal` and `none` can be
+    /// used in place of a pattern.
+    pub fn new(permalink: String) -> Self {
+        let permalink = match permalink.as_str() {
+            "pretty" => "/:categories/:year/:month/:day/:title/".to_string(),
+            "date" => "/:categories/:year/:month/:day/:title:output_ext".to_string(),
+            "ordinal" => "/:categories/:year/:y_day/:title:output_ext".to_string(),
+            "none" => "/:categories/:title:output_ext".to_string(),
+            _ => permalink,
+        };
+        Self { permalink }
     }
 
     fn get_path_with_no_extension(&self, og_path: &str) -> String {
@@ -17,54 +88,397 @@ impl ContentRouter {
             .to_string()
     }
 
-    pub fn route_post(&self, raw_post: &RawPost) -> String {
+    /// Path of the post relative to the posts directory, without extension.
+    /// `dir/index.md` is the page of a bundle and takes the directory's path,
+    /// so files next to it can be linked relatively.
+    fn get_content_path(&self, og_path: &str) -> String {
+        let path = self.get_path_with_no_extension(og_path);
+        let is_bundle = Path::new(&path)
+            .file_name()
+            .is_some_and(|name| name == "index");
+        if is_bundle {
+            parent_dir(&path).to_string()
+        } else {
+            path
+        }
+    }
+
+    fn placeholder_value(
+        &self,
+        placeholder: &str,
+        raw_post: &RawPost,
+        metadata: &Metadata,
+    ) -> Result<String, String> {
+        let categories = || {
+            metadata
+                .extras
+                .get("categories")
+                .or_else(|| metadata.extras.get("category"))
+                .map(value_to_segments)
+                .unwrap_or_default()
+        };
+        let path = self.get_content_path(&raw_post.og_path);
+
+        match placeholder {
+            "year" => Ok(metadata.date.format("%Y").to_string()),
+            "short_year" => Ok(metadata.date.format("%y").to_string()),
+            "month" => Ok(metadata.date.format("%m").to_string()),
+            "i_month" => Ok(metadata.date.format("%-m").to_string()),
+            "day" => Ok(metadata.date.format("%d").to_string()),
+            "i_day" => Ok(metadata.date.format("%-d").to_string()),
+            "y_day" => Ok(metadata.date.format("%j").to_string()),
+            // Like in Jekyll, the slug from the file name rather than the title
+            "title" | "slug" => Ok(metadata.slug.clone()),
+            "output_ext" => Ok(".html".to_string()),
+            "path" => Ok(path),
+            "category" => Ok(categories().into_iter().next().unwrap_or_default()),
+            "categories" => Ok(categories().join("/")),
+            _ => Err(format!("Unknown permalink placeholder :{}", placeholder)),
+        }
+    }
+
+    fn expand_permalink(
+        &self,
+        permalink: &str,
+        raw_post: &RawPost,
+        metadata: &Metadata,
+    ) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut rest = permalink;
+        while let Some(start) = rest.find(':') {
+            expanded.push_str(&rest[..start]);
+            let placeholder = &rest[start + 1..];
+            let end = placeholder
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(placeholder.len());
+            expanded.push_str(&self.placeholder_value(&placeholder[..end], raw_post, metadata)?);
+            rest = &placeholder[end..];
+        }
+        expanded.push_str(rest);
+
+        // Empty placeholders, like a post without categories, leave no gaps
+        let segments = expanded
+            .split('/')
+            .filter(|segment| !segment.is_empty() && *segment != ".")
+            .collect::<Vec<_>>();
+        // Pages are never written outside of the output directory
+        if segments
+            .iter()
+            .any(|segment| *segment == ".." || segment.contains('\\') || segment.contains(':'))
+        {
+            return Err(format!(
+                "Permalink {} leads outside of the output directory",
+                expanded
+            ));
+        }
+        let is_file = !expanded.ends_with('/')
+            && segments.last().is_some_and(|segment| segment.contains('.'));
+
+        let mut route = segments.join("/");
+        if !is_file {
+            if !route.is_empty() {
+                route.push('/');
+            }
+            route.push_str("index.html");
+        }
+        Ok(route)
+    }
+
+    /// Route of the post in the output directory, following the `permalink`
+    /// of its front matter when it has one or the site's pattern otherwise.
+    pub fn route_post(
+        &self,
+        raw_post: &RawPost,
+        metadata: &Metadata,
+    ) -> Result<String, BuildError> {
+        self.build_route(raw_post, metadata)
+            .map_err(|message| BuildError::Route {
+                path: raw_post.og_path.clone(),
+                message,
+            })
+    }
+
+    fn build_route(&self, raw_post: &RawPost, metadata: &Metadata) -> Result<String, String> {
         match raw_post.kind {
             ContentKind::Markdown => {
-                format!(
-                    "{}/{}/index.html",
-                    self.posts_path,
-                    self.get_path_with_no_extension(&raw_post.og_path)
-                )
+                let permalink = match metadata.extras.get("permalink") {
+                    Some(permalink) => permalink.as_str().ok_or("Permalink must be a string")?,
+                    None => &self.permalink,
+                };
+                self.expand_permalink(permalink, raw_post, metadata)
+            }
+        }
+    }
+
+    /// Routes for a non-content file found among the posts. It is copied next
+    /// to every post in the same directory (keyed by `og_path` in
+    /// `post_routes`) or, when there is none, mirrored under the fixed
+    /// directories the permalink starts with (`posts` for `/posts/:path/`).
+    pub fn route_asset(
+        &self,
+        asset_path: &str,
+        post_routes: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        let asset_dir = parent_dir(asset_path);
+        let file_name = asset_path.rsplit('/').next().unwrap_or(asset_path);
+
+        let mut routes = post_routes
+            .iter()
+            .filter(|(og_path, _)| parent_dir(og_path) == asset_dir)
+            .map(|(_, route)| match parent_dir(route) {
+                "" => file_name.to_string(),
+                route_dir => format!("{}/{}", route_dir, file_name),
+            })
+            .collect::<Vec<_>>();
+        routes.sort_unstable();
+        routes.dedup();
+
+        if routes.is_empty() {
+            let prefix = self.permalink.split(':').next().unwrap_or_default();
+            let prefix = parent_dir(prefix.trim_start_matches('/'));
+            if prefix.is_empty() {
+                routes.push(asset_path.to_string());
+            } else {
+                routes.push(format!("{}/{}", prefix, asset_path));
             }
         }
+        routes
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use crate::entities::FrontMatter;
+    use chrono::NaiveDate;
+
+    fn raw_post(og_path: &str) -> RawPost {
+        RawPost {
+            og_path: og_path.to_string(),
+            body_line: 1,
+            front_matter: FrontMatter::new(),
+            body: "test".to_string(),
+            kind: ContentKind::Markdown,
+        }
+    }
+
+    fn metadata(extras: FrontMatter) -> Metadata {
+        Metadata {
+            title: "Hello, World!".to_string(),
+            date: NaiveDate::from_ymd_opt(2025, 5, 4).unwrap(),
+            slug: "my-post".to_string(),
+            extras,
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn get_path_with_no_extension_test() {
-        let router = ContentRouter::new("posts".to_string());
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
         let path = router.get_path_with_no_extension("test.md");
         assert_eq!(path, "test");
     }
 
     #[test]
     fn test_route() {
-        let router = ContentRouter::new("posts".to_string());
-        let content = RawPost {
-            og_path: "test.md".to_string(),
-            front_matter: HashMap::new(),
-            body: "test".to_string(),
-            kind: ContentKind::Markdown,
-        };
-        let path = router.route_post(&content);
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
+        let path = router
+            .route_post(&raw_post("test.md"), &metadata(FrontMatter::new()))
+            .unwrap();
         assert_eq!(path, "posts/test/index.html");
     }
 
     #[test]
     fn test_route_with_nested_path() {
-        let router = ContentRouter::new("hello-world".to_string());
-        let content = RawPost {
-            og_path: "test/nested/post.md".to_string(),
-            front_matter: HashMap::new(),
-            body: "test".to_string(),
-            kind: ContentKind::Markdown,
-        };
-        let path = router.route_post(&content);
+        let router = ContentRouter::new("/hello-world/:path/".to_string());
+        let path = router
+            .route_post(
+                &raw_post("test/nested/post.md"),
+                &metadata(FrontMatter::new()),
+            )
+            .unwrap();
         assert_eq!(path, "hello-world/test/nested/post/index.html");
     }
+
+    #[test]
+    fn test_route_bundle_index() {
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
+        let path = router
+            .route_post(&raw_post("bundle/index.md"), &metadata(FrontMatter::new()))
+            .unwrap();
+        assert_eq!(path, "posts/bundle/index.html");
+    }
+
+    #[test]
+    fn test_route_date_permalink() {
+        let router = ContentRouter::new("/:year/:month/:day/:slug/".to_string());
+        let path = router
+            .route_post(
+                &raw_post("nested/My Post.md"),
+                &metadata(FrontMatter::new()),
+            )
+            .unwrap();
+        assert_eq!(path, "2025/05/04/my-post/index.html");
+    }
+
+    #[test]
+    fn test_route_category_permalink() {
+        let router = ContentRouter::new("/:categories/:title.html".to_string());
+        let extras = FrontMatter::from([("categories".to_string(), "jekyll update".into())]);
+        let path = router
+            .route_post(&raw_post("post.md"), &metadata(extras))
+            .unwrap();
+        assert_eq!(path, "jekyll/update/my-post.html");
+
+        let router = ContentRouter::new("/:category/:slug.html".to_string());
+        let extras = FrontMatter::from([(
+            "categories".to_string(),
+            serde_yaml::Value::Sequence(vec!["Release Notes".into(), "other".into()]),
+        )]);
+        let path = router
+            .route_post(&raw_post("post.md"), &metadata(extras))
+            .unwrap();
+        assert_eq!(path, "release-notes/my-post.html");
+
+        let path = router
+            .route_post(&raw_post("post.md"), &metadata(FrontMatter::new()))
+            .unwrap();
+        assert_eq!(path, "my-post.html");
+    }
+
+    #[test]
+    fn test_route_permalink_styles() {
+        let extras = || FrontMatter::from([("categories".to_string(), "jekyll update".into())]);
+        for (style, expected) in [
+            ("pretty", "jekyll/update/2025/05/04/my-post/index.html"),
+            ("date", "jekyll/update/2025/05/04/my-post.html"),
+            ("ordinal", "jekyll/update/2025/124/my-post.html"),
+            ("none", "jekyll/update/my-post.html"),
+            (
+                "/:short_year/:i_month/:i_day/:title:output_ext",
+                "25/5/4/my-post.html",
+            ),
+        ] {
+            let router = ContentRouter::new(style.to_string());
+            let path = router
+                .route_post(&raw_post("post.md"), &metadata(extras()))
+                .unwrap();
+            assert_eq!(path, expected, "{}", style);
+        }
+    }
+
+    #[test]
+    fn test_route_front_matter_permalink() {
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
+        let extras = FrontMatter::from([("permalink".to_string(), "/about/".into())]);
+        let path = router
+            .route_post(&raw_post("about.md"), &metadata(extras))
+            .unwrap();
+        assert_eq!(path, "about/index.html");
+
+        let extras = FrontMatter::from([("permalink".to_string(), "/404.html".into())]);
+        let path = router
+            .route_post(&raw_post("not-found.md"), &metadata(extras))
+            .unwrap();
+        assert_eq!(path, "404.html");
+    }
+
+    #[test]
+    fn test_route_outside_output_dir() {
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
+        for permalink in ["/../../escaped/", "/posts/./../../x.html", "/a/..\\b/"] {
+            let extras = FrontMatter::from([("permalink".to_string(), permalink.into())]);
+            let result = router.route_post(&raw_post("post.md"), &metadata(extras));
+            assert!(
+                result
+                    .unwrap_err()
+                    .to_string()
+                    .ends_with("leads outside of the output directory"),
+                "{}",
+                permalink
+            );
+        }
+
+        let extras = FrontMatter::from([("permalink".to_string(), "/./about/".into())]);
+        let path = router
+            .route_post(&raw_post("about.md"), &metadata(extras))
+            .unwrap();
+        assert_eq!(path, "about/index.html");
+    }
+
+    #[test]
+    fn test_route_unknown_placeholder() {
+        let router = ContentRouter::new("/:author/:slug/".to_string());
+        let result = router.route_post(&raw_post("post.md"), &metadata(FrontMatter::new()));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "post.md: Unknown permalink placeholder :author"
+        );
+    }
+
+    #[test]
+    fn test_route_asset() {
+        let router = ContentRouter::new(DEFAULT_PERMALINK.to_string());
+        let post_routes = BTreeMap::from([
+            (
+                "bundle/index.md".to_string(),
+                "posts/bundle/index.html".to_string(),
+            ),
+            ("first.md".to_string(), "posts/first/index.html".to_string()),
+            (
+                "second.md".to_string(),
+                "posts/second/index.html".to_string(),
+            ),
+        ]);
+
+        assert_eq!(
+            router.route_asset("bundle/image.png", &post_routes),
+            vec!["posts/bundle/image.png"]
+        );
+        assert_eq!(
+            router.route_asset("shared.png", &post_routes),
+            vec!["posts/first/shared.png", "posts/second/shared.png"]
+        );
+        assert_eq!(
+            router.route_asset("orphans/file.pdf", &post_routes),
+            vec!["posts/orphans/file.pdf"]
+        );
+
+        let router = ContentRouter::new("/:year/:slug/".to_string());
+        assert_eq!(
+            router.route_asset("orphans/file.pdf", &BTreeMap::new()),
+            vec!["orphans/file.pdf"]
+        );
+
+        // Posts of a directory sharing their route directory only get one copy
+        let router = ContentRouter::new("/:categories/:slug.html".to_string());
+        let post_routes = BTreeMap::from([
+            ("a.md".to_string(), "one/a.html".to_string()),
+            ("b.md".to_string(), "two/b.html".to_string()),
+            ("c.md".to_string(), "one/c.html".to_string()),
+        ]);
+        assert_eq!(
+            router.route_asset("pic.png", &post_routes),
+            vec!["one/pic.png", "two/pic.png"]
+        );
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Static   Sites "), "static-sites");
+        assert_eq!(slugify("日本 語"), "日本-語");
+        assert_eq!(slugify(".."), "");
+        assert_eq!(slugify("¿?"), "");
+        assert_eq!(slugify("../日本/"), "日本");
+    }
+
+    #[test]
+    fn test_route_url() {
+        assert_eq!(route_url("index.html"), "/");
+        assert_eq!(route_url("posts/hello/index.html"), "/posts/hello/");
+        assert_eq!(route_url("posts/hello.html"), "/posts/hello.html");
+        assert_eq!(route_url("posts/reindex.html"), "/posts/reindex.html");
+        assert_eq!(route_url("/feed.xml"), "/feed.xml");
+    }
 }
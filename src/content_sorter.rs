@@ -0,0 +1,153 @@
+use crate::entities::{PostOutput, SortBy, SortOrder};
+use serde_yaml::Value;
+use std::cmp::Ordering;
+
+fn sort_value(post: &PostOutput, field: &str) -> Option<Value> {
+    match field {
+        "title" => Some(Value::String(post.metadata.title.clone())),
+        "description" => Some(Value::String(post.metadata.description.clone())),
+        "date" => Some(Value::String(post.metadata.date.to_string())),
+        _ => post.metadata.extras.get(field).cloned(),
+    }
+}
+
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Number(_) => 0,
+        Value::String(_) => 1,
+        Value::Bool(_) => 2,
+        _ => 3,
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            let a = a.as_f64().unwrap_or_default();
+            let b = b.as_f64().unwrap_or_default();
+            a.total_cmp(&b)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+fn compare_field(a: &PostOutput, b: &PostOutput, field: &str, order: &SortOrder) -> Ordering {
+    // Posts without the field always go last, whatever the order
+    match (sort_value(a, field), sort_value(b, field)) {
+        (Some(a), Some(b)) => order.apply(compare_values(&a, &b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Sorts the posts in place following the site's `sort_by` and `order`
+/// settings. Ties are broken by route so the output is reproducible.
+pub fn sort_posts(posts: &mut [PostOutput], sort_by: &SortBy, order: Option<&SortOrder>) {
+    let order = order.cloned().unwrap_or_else(|| sort_by.default_order());
+
+    posts.sort_by(|a, b| {
+        let ordering = match sort_by {
+            SortBy::Date => order.apply(a.metadata.date.cmp(&b.metadata.date)),
+            SortBy::Title => order.apply(
+                a.metadata
+                    .title
+                    .to_lowercase()
+                    .cmp(&b.metadata.title.to_lowercase()),
+            ),
+            SortBy::Weight => compare_field(a, b, "weight", &order),
+            SortBy::Field(field) => compare_field(a, b, field, &order),
+        };
+        ordering.then_with(|| a.route.cmp(&b.route))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{FrontMatter, Metadata};
+    use chrono::NaiveDate;
+    use pretty_assertions::assert_eq;
+
+    fn post(route: &str, title: &str, date: &str, extras: FrontMatter) -> PostOutput {
+        PostOutput {
+            route: route.to_string(),
+            url: format!("/{}/", route),
+            source_path: format!("{}.md", route),
+            metadata: Metadata {
+                title: title.to_string(),
+                date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+                extras,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn routes(posts: &[PostOutput]) -> Vec<&str> {
+        posts.iter().map(|post| post.route.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_by_date_defaults_to_descending() {
+        let mut posts = vec![
+            post("b", "B", "2021-01-01", FrontMatter::new()),
+            post("c", "C", "2023-01-01", FrontMatter::new()),
+            post("a", "A", "2021-01-01", FrontMatter::new()),
+        ];
+        sort_posts(&mut posts, &SortBy::Date, None);
+        assert_eq!(routes(&posts), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_sort_by_title_ascending() {
+        let mut posts = vec![
+            post("1", "banana", "2021-01-01", FrontMatter::new()),
+            post("2", "Apple", "2022-01-01", FrontMatter::new()),
+            post("3", "cherry", "2023-01-01", FrontMatter::new()),
+        ];
+        sort_posts(&mut posts, &SortBy::Title, None);
+        assert_eq!(routes(&posts), vec!["2", "1", "3"]);
+
+        sort_posts(&mut posts, &SortBy::Title, Some(&SortOrder::Desc));
+        assert_eq!(routes(&posts), vec!["3", "1", "2"]);
+    }
+
+    #[test]
+    fn test_sort_by_weight_missing_last() {
+        let weight = |weight: i64| FrontMatter::from([("weight".to_string(), weight.into())]);
+        let mut posts = vec![
+            post("none", "None", "2021-01-01", FrontMatter::new()),
+            post("heavy", "Heavy", "2021-01-01", weight(10)),
+            post("light", "Light", "2021-01-01", weight(2)),
+        ];
+        sort_posts(&mut posts, &SortBy::Weight, None);
+        assert_eq!(routes(&posts), vec!["light", "heavy", "none"]);
+
+        sort_posts(&mut posts, &SortBy::Weight, Some(&SortOrder::Desc));
+        assert_eq!(routes(&posts), vec!["heavy", "light", "none"]);
+    }
+
+    #[test]
+    fn test_sort_by_front_matter_field() {
+        let series = |part: &str| FrontMatter::from([("part".to_string(), part.into())]);
+        let mut posts = vec![
+            post("b", "B", "2021-01-01", series("two")),
+            post("a", "A", "2021-01-01", series("one")),
+        ];
+        sort_posts(&mut posts, &SortBy::Field("part".to_string()), None);
+        assert_eq!(routes(&posts), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_sort_by_deserialize() {
+        let sort_by: SortBy = serde_yaml::from_str("date").unwrap();
+        assert_eq!(sort_by, SortBy::Date);
+        let sort_by: SortBy = serde_yaml::from_str("weight").unwrap();
+        assert_eq!(sort_by, SortBy::Weight);
+        let sort_by: SortBy = serde_yaml::from_str("series").unwrap();
+        assert_eq!(sort_by, SortBy::Field("series".to_string()));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::entities::{PostStats, ReadingConfig};
+use markdown::mdast::Node;
+
+/// Words of `text`, punctuation on its own not being one
+fn words(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// What was counted so far, code being read slower than prose
+#[derive(Default)]
+struct Counts {
+    prose_words: usize,
+    code_words: usize,
+    characters: usize,
+    images: usize,
+}
+
+impl Counts {
+    fn add_prose(&mut self, text: &str) {
+        self.prose_words += words(text);
+        self.characters += text.chars().count();
+    }
+
+    fn add_code(&mut self, text: &str) {
+        self.code_words += words(text);
+        self.characters += text.chars().count();
+    }
+}
+
+fn count(node: &Node, counts: &mut Counts) {
+    match node {
+        Node::Text(text) => counts.add_prose(&text.value),
+        Node::InlineCode(code) => counts.add_prose(&code.value),
+        Node::Code(code) => counts.add_code(&code.value),
+        Node::Math(math) => counts.add_code(&math.value),
+        Node::InlineMath(math) => counts.add_code(&math.value),
+        Node::Image(image) => {
+            counts.images += 1;
+            counts.add_prose(&image.alt);
+        }
+        Node::ImageReference(image) => {
+            counts.images += 1;
+            counts.add_prose(&image.alt);
+        }
+        _ => {}
+    }
+    for child in node.children().into_iter().flatten() {
+        count(child, counts);
+    }
+}
+
+/// Counts the words, characters and images of a markdown body through its
+/// syntax tree, so that markup, link targets and raw HTML aren't counted.
+/// The reading time is rounded up to the next minute.
+pub fn post_stats(
+    markdown: &str,
+    options: &markdown::ParseOptions,
+    reading_config: &ReadingConfig,
+) -> PostStats {
+    let mut counts = Counts::default();
+    if let Ok(tree) = markdown::to_mdast(markdown, options) {
+        count(&tree, &mut counts);
+    }
+
+    let minutes = counts.prose_words as f64 / reading_config.words_per_minute.max(1) as f64
+        + counts.code_words as f64 / reading_config.code_words_per_minute.max(1) as f64;
+    PostStats {
+        words: counts.prose_words + counts.code_words,
+        reading_minutes: minutes.ceil() as usize,
+        characters: counts.characters,
+        images: counts.images,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_post_stats() {
+        let markdown = "# Hello world\n\nSome *emphasized* [link](https://example.com) \
+                        and `code`.\n\n![A diagram](diagram.svg)\n\n<div>raw html</div>\n\n\
+                        ```rust\nfn main() {}\n```\n";
+        let stats = post_stats(
+            markdown,
+            &markdown::ParseOptions::default(),
+            &ReadingConfig::default(),
+        );
+        assert_eq!(
+            stats,
+            PostStats {
+                words: 11,
+                reading_minutes: 1,
+                characters: 62,
+                images: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reading_minutes() {
+        let reading_config = ReadingConfig {
+            words_per_minute: 10,
+            code_words_per_minute: 5,
+        };
+        let prose = "word ".repeat(25);
+        let code = format!("```\n{}\n```", "code ".repeat(10));
+        let options = markdown::ParseOptions::default();
+
+        assert_eq!(post_stats("", &options, &reading_config).reading_minutes, 0);
+        assert_eq!(
+            post_stats(&prose, &options, &reading_config).reading_minutes,
+            3
+        );
+        assert_eq!(
+            post_stats(&format!("{}\n\n{}", prose, code), &options, &reading_config)
+                .reading_minutes,
+            5
+        );
+    }
+}
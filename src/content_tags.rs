@@ -0,0 +1,132 @@
+use crate::content_router::{route_url, slugify};
+use crate::entities::{PostOutput, TagOutput};
+use std::collections::BTreeMap;
+
+pub const TAGS_INDEX_PATH: &str = "tags/index.html";
+
+/// Slug of the page of `tag`, made from a hash of the tag when nothing of it
+/// can go in a URL, like `..` or `¿?`
+pub fn tag_slug(tag: &str) -> String {
+    match slugify(tag) {
+        slug if slug.is_empty() => format!("tag-{}", &blake3::hash(tag.as_bytes()).to_hex()[..8]),
+        slug => slug,
+    }
+}
+
+/// Groups the posts by tag, keeping the order the posts are given in. Tags
+/// that slugify to the same value, like `Rust` and `rust`, share a page.
+pub fn collect_tags(posts: &[PostOutput]) -> Vec<TagOutput> {
+    let mut tags = BTreeMap::<String, TagOutput>::new();
+
+    for post in posts {
+        for tag in &post.metadata.tags {
+            let slug = tag_slug(tag);
+            let tag_output = tags.entry(slug.clone()).or_insert_with(|| {
+                let route = format!("tags/{}/index.html", slug);
+                TagOutput {
+                    name: tag.clone(),
+                    url: route_url(&route),
+                    route,
+                    slug,
+                    count: 0,
+                    posts: vec![],
+                }
+            });
+            if !tag_output
+                .posts
+                .iter()
+                .any(|tagged| tagged.route == post.route)
+            {
+                tag_output.count += 1;
+                tag_output.posts.push(post.clone());
+            }
+        }
+    }
+
+    let mut tags = tags.into_values().collect::<Vec<_>>();
+    tags.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.slug.cmp(&b.slug))
+    });
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::Metadata;
+    use pretty_assertions::assert_eq;
+
+    fn post(route: &str, tags: &[&str]) -> PostOutput {
+        PostOutput {
+            route: route.to_string(),
+            url: format!("/{}/", route),
+            source_path: format!("{}.md", route),
+            metadata: Metadata {
+                title: route.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_collect_tags() {
+        let posts = vec![
+            post("b", &["Rust", "Static Sites"]),
+            post("a", &["rust"]),
+            post("c", &["Static Sites", "static sites"]),
+        ];
+
+        let tags = collect_tags(&posts);
+        let summary = tags
+            .iter()
+            .map(|tag| {
+                (
+                    tag.name.as_str(),
+                    tag.slug.as_str(),
+                    tag.route.as_str(),
+                    tag.count,
+                    tag.posts
+                        .iter()
+                        .map(|post| post.route.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("Rust", "rust", "tags/rust/index.html", 2, vec!["b", "a"]),
+                (
+                    "Static Sites",
+                    "static-sites",
+                    "tags/static-sites/index.html",
+                    2,
+                    vec!["b", "c"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_tags_without_tags() {
+        assert!(collect_tags(&[post("a", &[])]).is_empty());
+    }
+
+    #[test]
+    fn test_tag_slug() {
+        assert_eq!(tag_slug("Static Sites"), "static-sites");
+        assert_eq!(tag_slug("日本 語"), "日本-語");
+        for tag in ["..", ".", "¿?", "/"] {
+            let slug = tag_slug(tag);
+            assert!(slug.starts_with("tag-") && slug.len() == 12, "{}", slug);
+            assert!(slug[4..].chars().all(|c| c.is_ascii_hexdigit()), "{}", slug);
+        }
+        assert_ne!(tag_slug(".."), tag_slug("¿?"));
+    }
+}
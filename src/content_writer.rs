@@ -1,24 +1,93 @@
+use crate::errors::BuildError;
+use std::path::{Component, Path, PathBuf};
+
 pub struct ContentWriter<'a> {
     output_dir: &'a str,
 }
 
+fn write_error(path: &str, error: impl std::fmt::Display) -> BuildError {
+    BuildError::Write {
+        path: path.to_string(),
+        message: error.to_string(),
+    }
+}
+
 impl<'a> ContentWriter<'a> {
     pub fn new(output_dir: &'a str) -> Self {
         Self { output_dir }
     }
 
-    pub fn clean_output_dir(&self) {
+    pub fn clean_output_dir(&self) -> Result<(), BuildError> {
         std::fs::remove_dir_all(self.output_dir).unwrap_or_default();
-        std::fs::create_dir_all(self.output_dir).unwrap();
+        std::fs::create_dir_all(self.output_dir).map_err(|e| write_error(self.output_dir, e))
     }
 
-    pub fn write_content(&self, path_from_root: &str, content: &str) {
-        let path = format!("{}/{}", self.output_dir, path_from_root);
+    /// Full path in the output directory, refusing paths that would lead
+    /// outside of it, like `../index.html` or `/etc/passwd`
+    fn output_path(&self, path_from_root: &str) -> Result<PathBuf, BuildError> {
+        let mut path = PathBuf::from(self.output_dir);
+        for component in Path::new(path_from_root).components() {
+            match component {
+                Component::Normal(segment) => path.push(segment),
+                Component::CurDir => {}
+                _ => {
+                    return Err(write_error(
+                        path_from_root,
+                        "leads outside of the output directory",
+                    ));
+                }
+            }
+        }
+        Ok(path)
+    }
+
+    /// Full path in the output directory, with its parent directories created
+    fn prepare_path(&self, path_from_root: &str) -> Result<PathBuf, BuildError> {
+        let path = self.output_path(path_from_root)?;
         // Create parent directories if they don't exist
-        if let Some(parent) = std::path::Path::new(&path).parent() {
-            std::fs::create_dir_all(parent).unwrap();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| write_error(path_from_root, e))?;
         }
-        std::fs::write(path, content).unwrap();
+        Ok(path)
+    }
+
+    pub fn write_content(&self, path_from_root: &str, content: &str) -> Result<(), BuildError> {
+        let path = self.prepare_path(path_from_root)?;
+        std::fs::write(path, content).map_err(|e| write_error(path_from_root, e))
+    }
+
+    pub fn copy_file<P: AsRef<Path>>(
+        &self,
+        source: &P,
+        path_from_root: &str,
+    ) -> Result<(), BuildError> {
+        let path = self.prepare_path(path_from_root)?;
+        std::fs::copy(source, path)
+            .map(|_| ())
+            .map_err(|e| write_error(path_from_root, e))
+    }
+
+    /// Removes a file written by a previous build, along with the
+    /// directories it leaves empty. A file that is already gone is not an
+    /// error.
+    pub fn remove_file(&self, path_from_root: &str) -> Result<(), BuildError> {
+        let output_dir = Path::new(self.output_dir);
+        let path = self.output_path(path_from_root)?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(write_error(path_from_root, e));
+            }
+            _ => {}
+        }
+
+        let mut directory = path.parent();
+        while let Some(dir) = directory
+            && dir != output_dir
+            && std::fs::remove_dir(dir).is_ok()
+        {
+            directory = dir.parent();
+        }
+        Ok(())
     }
 }
 
@@ -38,7 +107,7 @@ mod tests {
         fs::write(temp_dir.path().join("test2.txt"), "content2").unwrap();
 
         // Clean the directory
-        writer.clean_output_dir();
+        writer.clean_output_dir().unwrap();
 
         // Verify the directory is empty
         assert!(fs::read_dir(temp_dir.path()).unwrap().next().is_none());
@@ -51,7 +120,7 @@ mod tests {
 
         // Write a test post
         let content = "Test post content";
-        writer.write_content("test-post.md", content);
+        writer.write_content("test-post.md", content).unwrap();
 
         // Verify the file was created with correct content
         let file_path = temp_dir.path().join("test-post.md");
@@ -66,7 +135,9 @@ mod tests {
 
         // Write a test post in a nested directory
         let content = "Nested test post content";
-        writer.write_content("nested/path/test-post.md", content);
+        writer
+            .write_content("nested/path/test-post.md", content)
+            .unwrap();
 
         // Verify the file was created with correct content
         let file_path = temp_dir.path().join("nested/path/test-post.md");
@@ -82,7 +153,7 @@ mod tests {
         // Write to a deeply nested path
         let nested_path = "very/deeply/nested/path/file.txt";
         let content = "Test content";
-        writer.write_content(nested_path, content);
+        writer.write_content(nested_path, content).unwrap();
 
         // Verify all parent directories were created
         let mut current_path = temp_dir.path().to_path_buf();
@@ -105,4 +176,73 @@ mod tests {
         assert!(file_path.exists());
         assert_eq!(fs::read_to_string(file_path).unwrap(), content);
     }
+
+    #[test]
+    fn test_copy_file() {
+        let source_dir = TempDir::new().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let writer = ContentWriter::new(temp_dir.path().to_str().unwrap());
+
+        let source = source_dir.path().join("image.png");
+        fs::write(&source, [0x89, 0x50, 0x4e, 0x47]).unwrap();
+        writer.copy_file(&source, "posts/bundle/image.png").unwrap();
+
+        let file_path = temp_dir.path().join("posts/bundle/image.png");
+        assert_eq!(fs::read(file_path).unwrap(), vec![0x89, 0x50, 0x4e, 0x47]);
+    }
+
+    #[test]
+    fn test_remove_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = ContentWriter::new(temp_dir.path().to_str().unwrap());
+
+        writer.write_content("posts/old/index.html", "old").unwrap();
+        writer
+            .write_content("posts/kept/index.html", "kept")
+            .unwrap();
+        writer.remove_file("posts/old/index.html").unwrap();
+        writer
+            .remove_file("posts/never-written/index.html")
+            .unwrap();
+
+        assert!(!temp_dir.path().join("posts/old").exists());
+        assert!(temp_dir.path().join("posts/kept/index.html").exists());
+    }
+
+    #[test]
+    fn test_paths_outside_output_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().join("site");
+        let writer = ContentWriter::new(output_dir.to_str().unwrap());
+
+        fs::create_dir_all(temp_dir.path().join("outside")).unwrap();
+        fs::write(temp_dir.path().join("outside/index.html"), "keep").unwrap();
+        for path in ["../outside/index.html", "posts/../../outside/index.html"] {
+            let error = writer.remove_file(path).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("{}: leads outside of the output directory", path)
+            );
+            assert!(writer.write_content(path, "replaced").is_err());
+        }
+        let absolute = temp_dir.path().join("outside/index.html");
+        assert!(writer.remove_file(absolute.to_str().unwrap()).is_err());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("outside/index.html")).unwrap(),
+            "keep"
+        );
+    }
+
+    #[test]
+    fn test_write_content_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = ContentWriter::new(temp_dir.path().to_str().unwrap());
+
+        fs::write(temp_dir.path().join("file"), "not a directory").unwrap();
+        let error = writer
+            .write_content("file/index.html", "content")
+            .err()
+            .unwrap();
+        assert!(matches!(error, BuildError::Write { ref path, .. } if path == "file/index.html"));
+    }
 }
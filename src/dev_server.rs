@@ -0,0 +1,403 @@
+use notify::{RecursiveMode, Watcher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Request, Response, Server};
+
+/// Path polled by the reload script, answered once the site was rebuilt
+pub const RELOAD_PATH: &str = "/__crustdown/reload";
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(30);
+/// Changes arriving this close together trigger a single rebuild
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Long-polls the server with the build it was loaded from, and reloads the
+/// page as soon as a newer build is reported.
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+  var build = "__BUILD__";
+  function poll() {
+    fetch("/__crustdown/reload?since=" + build)
+      .then(function (response) { return response.text(); })
+      .then(function (latest) {
+        if (latest !== build) { location.reload(); } else { poll(); }
+      })
+      .catch(function () { setTimeout(poll, 1000); });
+  }
+  poll();
+})();
+</script>"#;
+
+const NOT_FOUND_PAGE: &str = "<!DOCTYPE html>\n<html><head><title>Not found</title></head>\
+<body><h1>404 Not found</h1></body></html>";
+
+/// Counts the builds made by the server, waking up the browsers waiting for
+/// a new one.
+#[derive(Default)]
+pub struct BuildCounter {
+    count: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl BuildCounter {
+    pub fn current(&self) -> u64 {
+        *self.count.lock().unwrap()
+    }
+
+    pub fn increment(&self) {
+        *self.count.lock().unwrap() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Waits until a build newer than `since` is available or `timeout`
+    /// elapses, returning the latest build.
+    pub fn wait_newer(&self, since: u64, timeout: Duration) -> u64 {
+        let count = self.count.lock().unwrap();
+        let (count, _) = self
+            .changed
+            .wait_timeout_while(count, timeout, |count| *count <= since)
+            .unwrap();
+        *count
+    }
+}
+
+/// Adds the reload script to an HTML page, right before `</body>` when there
+/// is one.
+pub fn inject_reload_script(html: &str, build: u64) -> String {
+    let script = RELOAD_SCRIPT.replace("__BUILD__", &build.to_string());
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], script, &html[index..]),
+        None => format!("{}{}", html, script),
+    }
+}
+
+/// What answers a request for `url` in the built site
+#[derive(Debug, PartialEq)]
+pub enum Resolved {
+    File(PathBuf),
+    /// The url points to a directory and misses its trailing slash
+    Redirect(String),
+    NotFound,
+}
+
+/// Maps a request url to a file of `site_dir`, serving `index.html` for
+/// directories. Urls escaping `site_dir` are never resolved.
+pub fn resolve_path(site_dir: &Path, url: &str) -> Resolved {
+    let raw_path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(raw_path);
+
+    let mut file = site_dir.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => file.push(part),
+            Component::CurDir => {}
+            _ => return Resolved::NotFound,
+        }
+    }
+
+    if file.is_dir() {
+        if !path.ends_with('/') {
+            return Resolved::Redirect(format!("{}/", raw_path));
+        }
+        file.push("index.html");
+    }
+    if file.is_file() {
+        Resolved::File(file)
+    } else {
+        Resolved::NotFound
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = text
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn content_type(path: &Path) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    match mime.type_() {
+        mime_guess::mime::TEXT => format!("{}; charset=utf-8", mime),
+        _ => mime.to_string(),
+    }
+}
+
+fn page_response(
+    file: &Path,
+    status: u16,
+    builds: &BuildCounter,
+) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type = content_type(file);
+    let data = std::fs::read(file).unwrap_or_default();
+    let data = if content_type.starts_with("text/html") {
+        inject_reload_script(&String::from_utf8_lossy(&data), builds.current()).into_bytes()
+    } else {
+        data
+    };
+    Response::from_data(data)
+        .with_status_code(status)
+        .with_header(header("Content-Type", &content_type))
+        .with_header(header("Cache-Control", "no-store"))
+}
+
+fn handle_request(request: Request, site_dir: &Path, builds: &BuildCounter) {
+    let url = request.url().to_string();
+
+    let response = if let Some(query) = url.strip_prefix(RELOAD_PATH) {
+        let since = query
+            .trim_start_matches("?since=")
+            .parse()
+            .unwrap_or_else(|_| builds.current());
+        let latest = builds.wait_newer(since, RELOAD_TIMEOUT);
+        Response::from_string(latest.to_string()).with_header(header("Cache-Control", "no-store"))
+    } else {
+        match resolve_path(site_dir, &url) {
+            Resolved::File(file) => page_response(&file, 200, builds),
+            Resolved::Redirect(location) => Response::from_data(Vec::new())
+                .with_status_code(301)
+                .with_header(header("Location", &location)),
+            Resolved::NotFound => {
+                let not_found = site_dir.join("404.html");
+                if not_found.is_file() {
+                    page_response(&not_found, 404, builds)
+                } else {
+                    Response::from_string(inject_reload_script(NOT_FOUND_PAGE, builds.current()))
+                        .with_status_code(404)
+                        .with_header(header("Content-Type", "text/html; charset=utf-8"))
+                }
+            }
+        }
+    };
+
+    if let Err(e) = request.respond(response) {
+        eprintln!("warning: failed to answer {}: {}", url, e);
+    }
+}
+
+/// Starts serving `site_dir` on `address` in the background, answering every
+/// request on its own thread so waiting reload requests don't block the
+/// others.
+pub fn serve(address: &str, site_dir: PathBuf, builds: Arc<BuildCounter>) -> Result<(), String> {
+    let server =
+        Server::http(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    let site_dir = Arc::new(site_dir);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let site_dir = Arc::clone(&site_dir);
+            let builds = Arc::clone(&builds);
+            thread::spawn(move || handle_request(request, &site_dir, &builds));
+        }
+    });
+    Ok(())
+}
+
+/// Absolute path of `path`, relative to `root` unless it is absolute, as the
+/// watcher reports it: without symbolic links when it exists
+fn watched_path(root: &Path, path: &Path) -> PathBuf {
+    let path = root
+        .join(path)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect::<PathBuf>();
+    path.canonicalize().unwrap_or(path)
+}
+
+/// Whether `event` is about a change to one of the `paths` or to something
+/// in them
+fn is_watched(event: &notify::Event, paths: &[PathBuf]) -> bool {
+    !event.kind.is_access()
+        && event
+            .paths
+            .iter()
+            .any(|changed| paths.iter().any(|path| changed.starts_with(path)))
+}
+
+/// Calls `rebuild` every time something changes in one of the `paths`,
+/// relative to `root`. The whole of `root` is watched so that paths created
+/// later, like a new `static` directory, are seen too. Bursts of changes,
+/// like an editor saving a file, only trigger one rebuild.
+pub fn watch<F: FnMut()>(root: &Path, paths: &[PathBuf], mut rebuild: F) -> Result<(), String> {
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+    let paths = paths
+        .iter()
+        .map(|path| watched_path(&root, path))
+        .collect::<Vec<_>>();
+
+    let (sender, receiver) = channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|e| format!("Failed to watch files: {}", e))?;
+    // Paths outside of the root, like a posts directory given as an absolute
+    // path, are watched on their own
+    let outside_root = paths
+        .iter()
+        .filter(|path| !path.starts_with(&root) && path.exists());
+    for path in std::iter::once(&root).chain(outside_root) {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", path.display(), e))?;
+    }
+
+    while wait_for_change(&receiver, &paths) {
+        rebuild();
+    }
+    Ok(())
+}
+
+/// Blocks until a change to one of the `paths` is reported and the following
+/// burst is over, returning false once the watcher is gone.
+fn wait_for_change(receiver: &Receiver<notify::Result<notify::Event>>, paths: &[PathBuf]) -> bool {
+    loop {
+        match receiver.recv() {
+            Ok(Ok(event)) if is_watched(&event, paths) => break,
+            Ok(_) => continue,
+            Err(_) => return false,
+        }
+    }
+    while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inject_reload_script() {
+        let html = inject_reload_script("<html><body><p>Hi</p></body></html>", 3);
+        assert!(html.starts_with("<html><body><p>Hi</p><script>"));
+        assert!(html.contains("var build = \"3\";"));
+        assert!(html.ends_with("</script></body></html>"));
+
+        let html = inject_reload_script("<p>Fragment</p>", 0);
+        assert!(html.starts_with("<p>Fragment</p><script>"));
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let site_dir = temp_dir.path();
+        fs::create_dir_all(site_dir.join("posts/my post")).unwrap();
+        fs::write(site_dir.join("index.html"), "index").unwrap();
+        fs::write(site_dir.join("posts/my post/index.html"), "post").unwrap();
+        fs::write(site_dir.join("feed.xml"), "feed").unwrap();
+
+        assert_eq!(
+            resolve_path(site_dir, "/"),
+            Resolved::File(site_dir.join("index.html"))
+        );
+        assert_eq!(
+            resolve_path(site_dir, "/feed.xml?utm=1"),
+            Resolved::File(site_dir.join("feed.xml"))
+        );
+        assert_eq!(
+            resolve_path(site_dir, "/posts/my%20post/"),
+            Resolved::File(site_dir.join("posts/my post/index.html"))
+        );
+        assert_eq!(
+            resolve_path(site_dir, "/posts/my%20post"),
+            Resolved::Redirect("/posts/my%20post/".to_string())
+        );
+        assert_eq!(resolve_path(site_dir, "/missing.html"), Resolved::NotFound);
+        assert_eq!(resolve_path(site_dir, "/../index.html"), Resolved::NotFound);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(
+            content_type(Path::new("index.html")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            content_type(Path::new("style.css")),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(content_type(Path::new("diagram.svg")), "image/svg+xml");
+        assert_eq!(content_type(Path::new("data")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_build_counter_wait_newer() {
+        let builds = Arc::new(BuildCounter::default());
+        assert_eq!(builds.wait_newer(0, Duration::from_millis(10)), 0);
+
+        let waiting = {
+            let builds = Arc::clone(&builds);
+            thread::spawn(move || builds.wait_newer(0, Duration::from_secs(10)))
+        };
+        builds.increment();
+        assert_eq!(waiting.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_watched_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("_posts")).unwrap();
+        let paths = [
+            watched_path(&root, Path::new("./_posts")),
+            watched_path(&root, Path::new("static")),
+            watched_path(&root, Path::new("_site.yml")),
+        ];
+        assert_eq!(
+            paths,
+            [
+                root.join("_posts"),
+                root.join("static"),
+                root.join("_site.yml")
+            ]
+        );
+
+        let event = |kind, path: PathBuf| notify::Event::new(kind).add_path(path);
+        let create = notify::EventKind::Create(notify::event::CreateKind::Any);
+        let access = notify::EventKind::Access(notify::event::AccessKind::Any);
+        assert!(is_watched(
+            &event(create, root.join("_posts/new.md")),
+            &paths
+        ));
+        // Created after the server started
+        assert!(is_watched(&event(create, root.join("static")), &paths));
+        assert!(is_watched(
+            &event(create, root.join("static/app.js")),
+            &paths
+        ));
+        assert!(is_watched(&event(create, root.join("_site.yml")), &paths));
+        assert!(!is_watched(
+            &event(access, root.join("_posts/new.md")),
+            &paths
+        ));
+        assert!(!is_watched(
+            &event(create, root.join("_site/index.html")),
+            &paths
+        ));
+        assert!(!is_watched(
+            &event(create, root.join("_site.yml.swp")),
+            &paths
+        ));
+    }
+}
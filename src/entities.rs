@@ -1,36 +1,352 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Front matter as parsed from YAML, keeping the typed values so templates
+/// can iterate lists and compare numbers or booleans. Fields are sorted by
+/// name so that templates see them in the same order on every build.
+pub type FrontMatter = BTreeMap<String, serde_yaml::Value>;
 
 pub enum ContentKind {
     Markdown,
 }
 
 pub struct RawPost {
-    pub front_matter: HashMap<String, String>,
+    pub front_matter: FrontMatter,
     pub body: String,
+    /// Line of the file the body starts on, from 1
+    pub body_line: usize,
     pub og_path: String,
     pub kind: ContentKind,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Metadata {
     pub title: String,
     pub description: String,
     pub date: NaiveDate,
+    /// URL friendly name of the post, used by the `:slug` permalink placeholder
+    pub slug: String,
     pub tags: Vec<String>,
-    pub extras: HashMap<String, String>,
+    /// Drafts are only built with `--drafts`
+    pub draft: bool,
+    /// Date from which the post is no longer published
+    pub expires: Option<NaiveDate>,
+    /// Every other front matter field, appearing next to the ones above in
+    /// templates
+    #[serde(flatten)]
+    pub extras: FrontMatter,
 }
 
-#[derive(Debug, Clone, Serialize)]
+/// A post as every template sees it, be it as `post` in a post layout or in
+/// the `posts` of the index and tag pages. Its fields are a stable contract
+/// with the site templates, documented in the readme.
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct PostOutput {
+    /// Path of the page in the output directory
     pub route: String,
+    /// Root relative URL of the page
+    pub url: String,
+    /// Path of the post relative to the posts directory
+    pub source_path: String,
     pub metadata: Metadata,
-    pub rendered_content: String,
+    /// The post body converted to HTML, without any template around it
+    pub content: String,
+    /// Headings of the post, empty with `toc: false` in its front matter
+    pub toc: Vec<TocEntry>,
+    pub excerpt: Excerpt,
+    pub stats: PostStats,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Figures about the body of a post
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PostStats {
+    /// Words of the text and code, markup and raw HTML left aside
+    pub words: usize,
+    /// Estimated time to read the post, rounded up
+    pub reading_minutes: usize,
+    pub characters: usize,
+    pub images: usize,
+}
+
+/// Beginning of a post: what is above `<!--more-->`, or its first paragraph
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Excerpt {
+    pub html: String,
+    pub text: String,
+}
+
+/// A heading of a post with the headings of lower level below it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub text: String,
+    /// `id` of the heading in the post content
+    pub id: String,
+    pub children: Vec<TocEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagOutput {
+    /// The tag as first written in the posts
+    pub name: String,
+    pub slug: String,
+    pub route: String,
+    pub url: String,
+    pub count: usize,
+    pub posts: Vec<PostOutput>,
+}
+
+/// One page of a listing split in pages, like the index or a tag page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Paginator<T> {
+    /// Number of the page, starting at 1
+    pub page: usize,
+    pub total_pages: usize,
+    pub per_page: usize,
+    pub total_items: usize,
+    /// Items of this page only
+    pub items: Vec<T>,
+    /// Path of the page in the output directory
+    pub route: String,
+    pub url: String,
+    pub previous_url: Option<String>,
+    pub next_url: Option<String>,
+    pub first_url: String,
+    pub last_url: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SiteConfig {
     pub title: String,
     pub description: String,
+    /// Globs, relative to the posts directory, of files and directories to skip
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// What the posts on the index are sorted by
+    #[serde(default)]
+    pub sort_by: SortBy,
+    /// Defaults to descending for dates and ascending for everything else
+    #[serde(default)]
+    pub order: Option<SortOrder>,
+    /// Layout for posts without a `layout` in their front matter, keyed by
+    /// directory relative to the posts directory (`""` for all of them)
+    #[serde(default)]
+    pub layouts: BTreeMap<String, String>,
+    /// Public address of the site, like `https://example.com`, used to build
+    /// absolute links
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// URL pattern for posts, such as `/:year/:month/:day/:slug/`
+    #[serde(default)]
+    pub permalink: Option<String>,
+    /// Number of posts per page of the index and tag pages, all of them
+    /// being on a single page when unset
+    #[serde(default)]
+    pub paginate: Option<usize>,
+    /// Feeds are generated whenever `base_url` is set, unless disabled here
+    #[serde(default)]
+    pub feed: Option<FeedConfig>,
+    /// Syntax highlighting of code blocks
+    #[serde(default)]
+    pub highlight: HighlightConfig,
+    /// Translates the Jekyll Liquid tags of posts before rendering them,
+    /// overridden by `liquid` in the front matter of a post
+    #[serde(default)]
+    pub liquid: bool,
+    /// Markdown extensions, overridden by `markdown` in the front matter of a
+    /// post
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    /// Reading speeds the reading time of posts is estimated with
+    #[serde(default)]
+    pub reading: ReadingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    pub enabled: bool,
+    /// Maximum number of posts in the feeds, newest first
+    pub limit: usize,
+    pub content: FeedContent,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            limit: 20,
+            content: FeedContent::Summary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// Only the post description
+    Summary,
+    /// The whole post rendered as HTML
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Feed {
+    pub site_url: String,
+    pub rss_url: String,
+    pub atom_url: String,
+    /// Date of the newest post, RFC 3339 for Atom
+    pub updated: String,
+    /// Date of the newest post, RFC 2822 for RSS
+    pub updated_rfc2822: String,
+    pub full_content: bool,
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedItem {
+    pub title: String,
+    pub link: String,
+    pub summary: String,
+    pub content: String,
+    pub published: String,
+    pub published_rfc2822: String,
+    pub tags: Vec<String>,
+    pub stats: PostStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum SortBy {
+    #[default]
+    Date,
+    Title,
+    Weight,
+    /// Any other front matter field
+    Field(String),
+}
+
+impl From<String> for SortBy {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "date" => SortBy::Date,
+            "title" => SortBy::Title,
+            "weight" => SortBy::Weight,
+            _ => SortBy::Field(value),
+        }
+    }
+}
+
+impl From<SortBy> for String {
+    fn from(value: SortBy) -> Self {
+        match value {
+            SortBy::Date => "date".to_string(),
+            SortBy::Title => "title".to_string(),
+            SortBy::Weight => "weight".to_string(),
+            SortBy::Field(field) => field,
+        }
+    }
+}
+
+impl SortBy {
+    pub fn default_order(&self) -> SortOrder {
+        match self {
+            SortBy::Date => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn apply(&self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighlightConfig {
+    pub enabled: bool,
+    /// One of the themes bundled with syntect, like `InspiredGitHub` or
+    /// `base16-ocean.dark`
+    pub theme: String,
+    pub style: HighlightStyle,
+    /// Show line numbers in every code block, fences can still turn them on
+    /// or off with `linenos` and `nolinenos`
+    pub line_numbers: bool,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        HighlightConfig {
+            enabled: false,
+            theme: "InspiredGitHub".to_string(),
+            style: HighlightStyle::default(),
+            line_numbers: false,
+        }
+    }
+}
+
+/// How highlighted code gets its colors
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    /// `style` attributes with the theme colors, nothing else is needed
+    #[default]
+    Inline,
+    /// CSS classes, styled by the stylesheet from `crustdown highlight-css`
+    Classes,
+}
+
+/// Markdown constructs enabled on top of CommonMark, which is all there is by
+/// default, and how the HTML is written
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    /// Every GitHub flavoured construct: tables, footnotes, strikethrough,
+    /// task lists and autolinks, each one can still be turned on or off on
+    /// its own below
+    pub gfm: bool,
+    pub tables: Option<bool>,
+    pub footnotes: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub task_lists: Option<bool>,
+    pub autolinks: Option<bool>,
+    /// `$inline$` and `$$` blocks of math
+    pub math: bool,
+    /// Keep the HTML written in posts instead of escaping it
+    pub raw_html: bool,
+    /// JSX and `{expressions}` like in MDX, which are left out of the output
+    pub mdx: bool,
+    /// Add a `#` link to itself to every heading
+    pub heading_anchors: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingConfig {
+    pub words_per_minute: usize,
+    /// Code takes longer to read than prose
+    pub code_words_per_minute: usize,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        ReadingConfig {
+            words_per_minute: 200,
+            code_words_per_minute: 100,
+        }
+    }
 }
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::path::Path;
+
+/// Everything that can go wrong while building a site. Errors tied to a file
+/// carry its path so a build can report all of them at once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// The site configuration is missing, invalid or inconsistent
+    Config { message: String },
+    /// A source file or directory could not be read
+    Read { path: String, message: String },
+    /// The front matter of a post could not be split or parsed, `line` being
+    /// the line of the post where the problem is when it is known
+    FrontMatter {
+        path: String,
+        line: Option<usize>,
+        message: String,
+    },
+    /// The front matter is valid but misses or mistypes a field
+    Metadata { path: String, message: String },
+    /// The post route could not be built from the permalink
+    Route { path: String, message: String },
+    /// A template is missing or failed to render
+    Render { path: String, message: String },
+    /// An output file could not be written
+    Write { path: String, message: String },
+}
+
+impl BuildError {
+    pub fn message(&self) -> &str {
+        match self {
+            BuildError::Config { message }
+            | BuildError::Read { message, .. }
+            | BuildError::FrontMatter { message, .. }
+            | BuildError::Metadata { message, .. }
+            | BuildError::Route { message, .. }
+            | BuildError::Render { message, .. }
+            | BuildError::Write { message, .. } => message,
+        }
+    }
+
+    /// Path of the file the error is about, none for configuration errors
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            BuildError::Config { .. } => None,
+            BuildError::Read { path, .. }
+            | BuildError::FrontMatter { path, .. }
+            | BuildError::Metadata { path, .. }
+            | BuildError::Route { path, .. }
+            | BuildError::Render { path, .. }
+            | BuildError::Write { path, .. } => Some(path),
+        }
+    }
+
+    /// The same error with its path taken as relative to `dir`, to report
+    /// errors about posts with the directory they are in
+    pub fn in_dir(mut self, dir: &Path) -> Self {
+        match &mut self {
+            BuildError::Config { .. } => {}
+            BuildError::Read { path, .. }
+            | BuildError::FrontMatter { path, .. }
+            | BuildError::Metadata { path, .. }
+            | BuildError::Route { path, .. }
+            | BuildError::Render { path, .. }
+            | BuildError::Write { path, .. } => *path = dir.join(&*path).display().to_string(),
+        }
+        self
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Config { .. } => write!(f, "{}", self.message()),
+            BuildError::FrontMatter {
+                path,
+                line: Some(line),
+                ..
+            } => write!(f, "{}:{}: {}", path, line, self.message()),
+            BuildError::Read { path, .. }
+            | BuildError::FrontMatter { path, .. }
+            | BuildError::Metadata { path, .. }
+            | BuildError::Route { path, .. }
+            | BuildError::Render { path, .. }
+            | BuildError::Write { path, .. } => write!(f, "{}: {}", path, self.message()),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let error = BuildError::FrontMatter {
+            path: "post.md".to_string(),
+            line: Some(3),
+            message: "Invalid front matter".to_string(),
+        };
+        assert_eq!(error.to_string(), "post.md:3: Invalid front matter");
+
+        let error = BuildError::Metadata {
+            path: "post.md".to_string(),
+            message: "Title is required".to_string(),
+        };
+        assert_eq!(error.to_string(), "post.md: Title is required");
+        assert_eq!(error.message(), "Title is required");
+
+        let error = BuildError::Config {
+            message: "base_url is required to generate feeds".to_string(),
+        };
+        assert_eq!(error.to_string(), "base_url is required to generate feeds");
+    }
+
+    #[test]
+    fn test_in_dir() {
+        let error = BuildError::FrontMatter {
+            path: "nested/post.md".to_string(),
+            line: Some(3),
+            message: "Invalid front matter".to_string(),
+        }
+        .in_dir(Path::new("_drafts"));
+        assert_eq!(error.path(), Some("_drafts/nested/post.md"));
+        assert_eq!(
+            error.to_string(),
+            "_drafts/nested/post.md:3: Invalid front matter"
+        );
+
+        let error = BuildError::Config {
+            message: "Invalid site config".to_string(),
+        };
+        assert_eq!(error.clone().in_dir(Path::new("_posts")), error);
+        assert_eq!(error.path(), None);
+    }
+}
@@ -1,81 +1,619 @@
-use anyhow::Result;
-use clap::Parser;
-use std::path::Path;
+use anyhow::{Result, bail};
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+mod content_cache;
+mod content_excerpt;
+mod content_feed;
+mod content_filter;
+mod content_headings;
+mod content_highlight;
+mod content_liquid;
+mod content_paginator;
 mod content_parser;
 mod content_reader;
 mod content_render;
 mod content_router;
+mod content_sorter;
+mod content_stats;
+mod content_tags;
 mod content_writer;
+mod dev_server;
 mod entities;
+mod errors;
 
+use content_cache::{BuildCache, Fingerprint};
+use content_feed::{ATOM_PATH, RSS_PATH, build_feed};
+use content_filter::{PublishOptions, is_published};
+use content_highlight::Highlighter;
+use content_paginator::paginate;
 use content_parser::{parse_content, parse_metadata, parse_site_config};
-use content_reader::read_content;
+use content_reader::{read_assets, read_content, read_files};
 use content_render::Rendererer;
-use content_router::ContentRouter;
+use content_router::{ContentRouter, DEFAULT_PERMALINK, route_url};
+use content_sorter::sort_posts;
+use content_tags::{TAGS_INDEX_PATH, collect_tags};
 use content_writer::ContentWriter;
+use dev_server::BuildCounter;
 use entities::PostOutput;
+use errors::BuildError;
 
 /// A static site generator for markdown content
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the root directory
-    #[arg(short, long, default_value = ".")]
+    #[arg(short, long, global = true, default_value = ".")]
     root_dir: String,
 
     /// Path to the posts directory
-    #[arg(short, long, default_value = "_posts")]
+    #[arg(short, long, global = true, default_value = "_posts")]
     posts_dir: String,
 
     /// Path to the output directory
-    #[arg(short, long, default_value = "_site")]
+    #[arg(short, long, global = true, default_value = "_site")]
     output_dir: String,
 
     /// Path to the site configuration file
-    #[arg(short, long, default_value = "_site.yml")]
+    #[arg(short, long, global = true, default_value = "_site.yml")]
     site_config: String,
+
+    /// Path to the directory with templates overriding the built-in ones
+    #[arg(short, long, global = true, default_value = "_templates")]
+    templates_dir: String,
+
+    /// Path to the directory with static files copied as-is to the output
+    #[arg(long, global = true, default_value = "static")]
+    static_dir: String,
+
+    /// Path to the directory with unpublished posts, only built with --drafts
+    #[arg(long, global = true, default_value = "_drafts")]
+    drafts_dir: String,
+
+    /// Build drafts, future and expired posts too, useful for local previews
+    #[arg(long, global = true)]
+    drafts: bool,
+
+    /// Build posts dated in the future
+    #[arg(long, global = true)]
+    future: bool,
+
+    /// Write every page that built even when others failed
+    #[arg(long, global = true)]
+    keep_going: bool,
+
+    /// Number of threads parsing and rendering the site, all cores by default
+    #[arg(short, long, global = true, default_value_t = 0)]
+    jobs: usize,
+
+    /// Render and write everything again instead of only what changed
+    #[arg(long, global = true)]
+    clean: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build the site, the default when no command is given
+    Build,
+    /// Build the site and serve it locally, rebuilding and reloading open
+    /// pages whenever the content changes
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = 4000)]
+        port: u16,
+    },
+    /// Print the stylesheet for code highlighted with `style: classes`
+    HighlightCss {
+        /// Theme to use instead of the one in the site configuration
+        #[arg(long)]
+        theme: Option<String>,
+    },
+}
+
+/// What an output file needs to be up to date
+enum Output {
+    /// It was built from the same inputs by the previous build
+    Fresh,
+    Page(String),
+    Copy(PathBuf),
+}
 
+/// Claims `route` for the output built from `source`, failing when another
+/// output already has it instead of letting one replace the other
+fn claim_route(
+    sources: &mut BTreeMap<String, String>,
+    route: &str,
+    source: &str,
+) -> Result<(), BuildError> {
+    match sources.get(route) {
+        Some(other) => Err(BuildError::Route {
+            path: source.to_string(),
+            message: format!("{} is already written by {}", route, other),
+        }),
+        None => {
+            sources.insert(route.to_string(), source.to_string());
+            Ok(())
+        }
+    }
+}
+
+/// Builds the site, reporting every error found instead of stopping at the
+/// first one. Nothing is written unless the whole site builds or
+/// `--keep-going` is set, in which case everything that did build is written.
+///
+/// Outputs whose inputs didn't change since the last build are neither
+/// rendered nor written again, and outputs the site doesn't have anymore are
+/// removed, while outputs that failed to build keep their last good version.
+fn build(args: &Args) -> Result<(), Vec<BuildError>> {
     let root_dir = Path::new(&args.root_dir);
     let site_config_path = root_dir.join(&args.site_config);
     let posts_dir = root_dir.join(&args.posts_dir);
+    let templates_dir = root_dir.join(&args.templates_dir);
+    let static_dir = root_dir.join(&args.static_dir);
+    let drafts_dir = root_dir.join(&args.drafts_dir);
+    let output_dir = Path::new(&args.output_dir);
 
-    let site_config = parse_site_config(&site_config_path).unwrap();
+    let site_config = parse_site_config(&site_config_path).map_err(|e| vec![e])?;
 
-    let posts = read_content(&posts_dir);
+    let mut errors = Vec::new();
 
+    let mut posts = read_content(&posts_dir, &site_config.ignore).map_err(|e| vec![e])?;
+    let assets = read_assets(&posts_dir, &site_config.ignore).map_err(|e| vec![e])?;
+    // Drafts are keyed by their path in the drafts directory like posts are
+    // in theirs, so a draft can't have the path of a post
+    let mut draft_paths = BTreeSet::new();
+    let mut draft_assets = Vec::new();
+    if args.drafts && drafts_dir.is_dir() {
+        for (og_path, source) in
+            read_content(&drafts_dir, &site_config.ignore).map_err(|e| vec![e])?
+        {
+            if posts.contains_key(&og_path) {
+                errors.push(BuildError::Read {
+                    path: Path::new(&args.drafts_dir)
+                        .join(&og_path)
+                        .display()
+                        .to_string(),
+                    message: format!("{} is in the posts directory too", og_path),
+                });
+                continue;
+            }
+            draft_paths.insert(og_path.clone());
+            posts.insert(og_path, source);
+        }
+        draft_assets = read_assets(&drafts_dir, &site_config.ignore).map_err(|e| vec![e])?;
+    }
+    let static_files = read_files(&static_dir).map_err(|e| vec![e])?;
+    // Posts are reported by their path relative to the root directory, a post
+    // and a draft sharing their path in their own directory
+    let source_dir = |og_path: &str| match draft_paths.contains(og_path) {
+        true => Path::new(&args.drafts_dir),
+        false => Path::new(&args.posts_dir),
+    };
+    let in_source_dir = |e: BuildError| match e.path().map(source_dir) {
+        Some(dir) => e.in_dir(dir),
+        None => e,
+    };
+
+    let publish_options = PublishOptions {
+        drafts: args.drafts,
+        future: args.future,
+        today: Local::now().date_naive(),
+    };
     let posts = posts
-        .iter()
-        .map(|(file_name, content)| parse_content(file_name, content).unwrap());
-
-    let router = ContentRouter::new("posts".to_string());
-    let content_renderer = Rendererer::new();
-
-    let posts = posts.map(|post| {
-        let route = router.route_post(&post);
-        let rendered_post = content_renderer.render_post(&post, &site_config);
-        let metadata = parse_metadata(post.front_matter).unwrap();
-        PostOutput {
-            route,
-            metadata,
-            rendered_content: rendered_post,
+        .par_iter()
+        .map(|(file_name, source)| {
+            let post = parse_content(file_name, source)?;
+            let metadata = parse_metadata(&post.og_path, post.front_matter.clone())?;
+            Ok((post, metadata, source))
+        })
+        .map(|result| result.map_err(in_source_dir))
+        .collect::<Vec<_>>();
+    let posts = keep_successes(posts, &mut errors)
+        .into_iter()
+        .filter(|(_, metadata, _)| is_published(metadata, &publish_options))
+        .collect::<Vec<_>>();
+
+    let router = ContentRouter::new(
+        site_config
+            .permalink
+            .clone()
+            .unwrap_or(DEFAULT_PERMALINK.to_string()),
+    );
+    let highlighter = Highlighter::new(&site_config.highlight).map_err(|e| vec![e])?;
+    let content_renderer = Rendererer::new(Some(&templates_dir)).with_highlighter(highlighter);
+
+    let previous_cache = if args.clean {
+        None
+    } else {
+        BuildCache::load(output_dir)
+    };
+    let is_fresh = |path: &str, fingerprint: &str| {
+        previous_cache
+            .as_ref()
+            .is_some_and(|cache| cache.is_fresh(output_dir, path, fingerprint))
+    };
+    let site_fingerprint = Fingerprint::new().add_json(&site_config).finish();
+    let template_fingerprint = |template_name: &str| {
+        let mut fingerprint = Fingerprint::new();
+        for (name, source) in content_renderer.template_dependencies(template_name) {
+            fingerprint.add(name).add(source);
+        }
+        fingerprint.finish()
+    };
+
+    let rendered_posts = posts
+        .into_par_iter()
+        .map(|(mut post, metadata, source)| {
+            let route = router.route_post(&post, &metadata)?;
+            let mut post_output = PostOutput {
+                url: route_url(&route),
+                route,
+                source_path: post.og_path.clone(),
+                metadata,
+                ..Default::default()
+            };
+            let mut liquid_warnings = Vec::new();
+            let mut post_fingerprint = Fingerprint::new();
+            post_fingerprint
+                .add(&post.og_path)
+                .add(source)
+                .add(&post_output.route);
+            if content_renderer.uses_liquid(&post_output, &site_config) {
+                let liquid = content_renderer.render_liquid(&post, &post_output, &site_config)?;
+                for (name, source) in liquid.templates {
+                    post_fingerprint.add(name).add(source);
+                }
+                post.body = liquid.body;
+                liquid_warnings = liquid.warnings;
+            }
+            let markdown_config = content_renderer.markdown_config(&post_output, &site_config)?;
+            let rendered =
+                content_renderer.render_content(&post, &markdown_config, &site_config.reading)?;
+            post_output.content = rendered.html;
+            // `toc: false` in the front matter leaves the table of contents out
+            if post_output
+                .metadata
+                .extras
+                .get("toc")
+                .and_then(|toc| toc.as_bool())
+                != Some(false)
+            {
+                post_output.toc = rendered.toc;
+            }
+            if post_output.metadata.description.is_empty() {
+                post_output.metadata.description = rendered.excerpt.text.clone();
+            }
+            post_output.excerpt = rendered.excerpt;
+            post_output.stats = rendered.stats;
+            let post_fingerprint = post_fingerprint.finish();
+            let layout = content_renderer.post_layout(&post_output, &site_config)?;
+            let fingerprint = Fingerprint::new()
+                .add(&site_fingerprint)
+                .add(template_fingerprint(&format!("{}.html", layout)))
+                .add(&post_fingerprint)
+                .finish();
+
+            let output = if is_fresh(&post_output.route, &fingerprint) {
+                Output::Fresh
+            } else {
+                Output::Page(content_renderer.render_post(&post_output, &site_config)?)
+            };
+            Ok((
+                post.og_path,
+                post_fingerprint,
+                post_output,
+                fingerprint,
+                output,
+                liquid_warnings,
+            ))
+        })
+        .map(|result| result.map_err(in_source_dir))
+        .collect::<Vec<_>>();
+
+    // Every output file with the fingerprint of its inputs, and what it is
+    // built from
+    let mut outputs = BTreeMap::new();
+    let mut sources = BTreeMap::new();
+    // Fingerprint of every post, pages listing posts depend on all of them
+    let mut post_fingerprints = BTreeMap::new();
+    let mut post_routes = BTreeMap::new();
+    let mut draft_routes = BTreeMap::new();
+    let mut posts = Vec::new();
+    for (og_path, post_fingerprint, post, fingerprint, output, liquid_warnings) in
+        keep_successes(rendered_posts, &mut errors)
+    {
+        let source_path = source_dir(&og_path).join(&og_path);
+        for (line, message) in liquid_warnings {
+            eprintln!("warning: {}:{}: {}", source_path.display(), line, message);
+        }
+        if let Err(e) = claim_route(
+            &mut sources,
+            &post.route,
+            &source_path.display().to_string(),
+        ) {
+            errors.push(e);
+            continue;
         }
+        outputs.insert(post.route.clone(), (fingerprint, output));
+        post_fingerprints.insert(og_path.clone(), post_fingerprint);
+        if draft_paths.contains(&og_path) {
+            draft_routes.insert(og_path, post.route.clone());
+        } else {
+            post_routes.insert(og_path, post.route.clone());
+        }
+        posts.push(post);
+    }
+    sort_posts(&mut posts, &site_config.sort_by, site_config.order.as_ref());
+
+    let mut posts_fingerprint = Fingerprint::new();
+    posts_fingerprint.add(&site_fingerprint);
+    for fingerprint in post_fingerprints.values() {
+        posts_fingerprint.add(fingerprint);
+    }
+    let posts_fingerprint = posts_fingerprint.finish();
+
+    let content_renderer = &content_renderer;
+    let site_config = &site_config;
+    let tags = collect_tags(&posts);
+    let feed = build_feed(&posts, site_config).unwrap_or_else(|e| {
+        errors.push(e);
+        None
     });
 
+    // Pages built from all the posts, as (route, template, source, render)
+    type Render<'a> = Box<dyn Fn() -> Result<String, BuildError> + Send + Sync + 'a>;
+    let mut listings: Vec<(String, &str, String, Render)> = vec![(
+        TAGS_INDEX_PATH.to_string(),
+        "tags.html",
+        "the tags index".to_string(),
+        Box::new(|| content_renderer.render_tags(&tags, site_config)),
+    )];
+    for paginator in paginate(&posts, site_config.paginate, "index.html") {
+        let posts = &posts;
+        listings.push((
+            paginator.route.clone(),
+            "index.html",
+            format!("page {} of the index", paginator.page),
+            Box::new(move || content_renderer.render_index(posts, &paginator, site_config)),
+        ));
+    }
+    for tag in &tags {
+        for paginator in paginate(&tag.posts, site_config.paginate, &tag.route) {
+            listings.push((
+                paginator.route.clone(),
+                "tag.html",
+                format!("page {} of tag {}", paginator.page, tag.name),
+                Box::new(move || content_renderer.render_tag(tag, &paginator, site_config)),
+            ));
+        }
+    }
+    if let Some(feed) = &feed {
+        for feed_path in [RSS_PATH, ATOM_PATH] {
+            listings.push((
+                feed_path.to_string(),
+                feed_path,
+                format!("the {} feed", feed_path),
+                Box::new(move || content_renderer.render_feed(feed_path, feed, site_config)),
+            ));
+        }
+    }
+    let listings = listings
+        .into_par_iter()
+        .map(|(path, template_name, source, render)| {
+            let fingerprint = Fingerprint::new()
+                .add(&posts_fingerprint)
+                .add(template_fingerprint(template_name))
+                .finish();
+            let output = if is_fresh(&path, &fingerprint) {
+                Output::Fresh
+            } else {
+                Output::Page(render()?)
+            };
+            Ok((path, source, fingerprint, output))
+        })
+        .collect::<Vec<_>>();
+
+    let copies = static_files
+        .iter()
+        .map(|file| (static_dir.join(file), file.clone()))
+        .chain(
+            [
+                (&posts_dir, &assets, &post_routes),
+                (&drafts_dir, &draft_assets, &draft_routes),
+            ]
+            .into_iter()
+            .flat_map(|(dir, assets, routes)| {
+                assets.iter().flat_map(|asset| {
+                    let source = dir.join(asset);
+                    router
+                        .route_asset(asset, routes)
+                        .into_iter()
+                        .map(move |route| (source.clone(), route))
+                })
+            }),
+        )
+        .collect::<Vec<_>>();
+    let copies = copies
+        .into_par_iter()
+        .map(|(source, path)| {
+            let bytes = std::fs::read(&source).map_err(|e| BuildError::Read {
+                path: source.display().to_string(),
+                message: e.to_string(),
+            })?;
+            let fingerprint = Fingerprint::new().add(bytes).finish();
+            let output = if is_fresh(&path, &fingerprint) {
+                Output::Fresh
+            } else {
+                Output::Copy(source.clone())
+            };
+            Ok((path, source.display().to_string(), fingerprint, output))
+        })
+        .collect::<Vec<_>>();
+
+    for (path, source, fingerprint, output) in keep_successes(listings, &mut errors)
+        .into_iter()
+        .chain(keep_successes(copies, &mut errors))
+    {
+        match claim_route(&mut sources, &path, &source) {
+            Ok(()) => {
+                outputs.insert(path, (fingerprint, output));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if !errors.is_empty() && !args.keep_going {
+        return Err(errors);
+    }
+
     let content_writer = ContentWriter::new(&args.output_dir);
-    content_writer.clean_output_dir();
+    if previous_cache.is_none() {
+        content_writer.clean_output_dir().map_err(|e| {
+            errors.push(e);
+            errors.clone()
+        })?;
+    }
+
+    let written = outputs
+        .into_par_iter()
+        .map(|(path, (fingerprint, output))| {
+            match output {
+                Output::Fresh => {}
+                Output::Page(content) => content_writer.write_content(&path, &content)?,
+                Output::Copy(source) => content_writer.copy_file(&source, &path)?,
+            }
+            Ok((path, fingerprint))
+        })
+        .collect::<Vec<_>>();
+    let mut cache = BuildCache::new();
+    for (path, fingerprint) in keep_successes(written, &mut errors) {
+        cache.insert(&path, fingerprint, &sources[&path]);
+    }
+    if let Some(previous_cache) = &previous_cache {
+        // What failed keeps its last good output, only outputs whose source
+        // is gone are stale
+        let failed = errors
+            .iter()
+            .filter_map(|e| e.path().map(str::to_string))
+            .collect();
+        cache.keep_failed(previous_cache, &failed);
+        for path in previous_cache.stale_outputs(&cache) {
+            if let Err(e) = content_writer.remove_file(path) {
+                errors.push(e);
+            }
+        }
+    }
+    if let Err(e) = cache.save(output_dir) {
+        errors.push(e);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The successful values of `results`, in order, their errors being moved to
+/// `errors`
+fn keep_successes<T>(results: Vec<Result<T, BuildError>>, errors: &mut Vec<BuildError>) -> Vec<T> {
+    let mut values = Vec::with_capacity(results.len());
+    for result in results {
+        match result {
+            Ok(value) => values.push(value),
+            Err(e) => errors.push(e),
+        }
+    }
+    values
+}
 
-    let posts = posts.collect::<Vec<_>>();
+fn report_errors(errors: &[BuildError]) {
+    for error in errors {
+        eprintln!("error: {}", error);
+    }
+}
 
-    let index_content = content_renderer.render_index(&posts, &site_config);
-    content_writer.write_content("index.html", &index_content);
-    for post in posts {
-        content_writer.write_content(&post.route, &post.rendered_content);
+/// Builds the site, serves the output directory and rebuilds it whenever the
+/// posts, templates, static files or site configuration change. Failed
+/// rebuilds are reported and the last good build stays served.
+fn serve(args: &Args, host: &str, port: u16) -> Result<()> {
+    let builds = Arc::new(BuildCounter::default());
+    if let Err(errors) = build(args) {
+        report_errors(&errors);
     }
 
+    let address = format!("{}:{}", host, port);
+    dev_server::serve(
+        &address,
+        args.output_dir.clone().into(),
+        Arc::clone(&builds),
+    )
+    .map_err(anyhow::Error::msg)?;
+    println!("Serving {} on http://{}/", args.output_dir, address);
+
+    let watched = [
+        &args.posts_dir,
+        &args.templates_dir,
+        &args.static_dir,
+        &args.drafts_dir,
+        &args.site_config,
+    ]
+    .map(PathBuf::from);
+    dev_server::watch(Path::new(&args.root_dir), &watched, || match build(args) {
+        Ok(()) => {
+            builds.increment();
+            println!("Rebuilt the site");
+        }
+        Err(errors) => report_errors(&errors),
+    })
+    .map_err(anyhow::Error::msg)
+}
+
+/// Prints the stylesheet of the highlight theme of the site, or of `theme`
+fn highlight_css(args: &Args, theme: Option<&String>) -> Result<()> {
+    let site_config_path = Path::new(&args.root_dir).join(&args.site_config);
+    let mut config = if site_config_path.is_file() {
+        parse_site_config(&site_config_path)?.highlight
+    } else {
+        Default::default()
+    };
+    if let Some(theme) = theme {
+        config.theme = theme.clone();
+    }
+    config.enabled = true;
+
+    if let Some(highlighter) = Highlighter::new(&config)? {
+        print!("{}", highlighter.stylesheet());
+    }
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.jobs)
+        .build_global()?;
+
+    match &args.command {
+        Some(Command::Serve { host, port }) => serve(&args, host, *port),
+        Some(Command::HighlightCss { theme }) => highlight_css(&args, theme.as_ref()),
+        Some(Command::Build) | None => {
+            if let Err(errors) = build(&args) {
+                report_errors(&errors);
+                bail!("Build failed with {} error(s)", errors.len());
+            }
+            Ok(())
+        }
+    }
+}
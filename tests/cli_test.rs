@@ -1,13 +1,31 @@
 use anyhow::Result;
 use assert_cmd::Command;
+use assert_cmd::assert::Assert;
 use pretty_assertions::assert_eq;
 use std::fs;
+use std::path::Path;
 use tempfile::TempDir;
 
 static INPUT_CONTENT_LOCATION: &str = "tests/file_fixtures/inputs";
 static INPUT_POST_LOCATION: &str = "posts";
 static OUTPUT_POST_LOCATION: &str = "tests/file_fixtures/outputs";
 
+/// Builds the site at `root` into `out`, with `extra` arguments
+fn build(root: &Path, out: &Path, extra: &[&str]) -> Assert {
+    Command::cargo_bin("crustdown")
+        .unwrap()
+        .arg("--root-dir")
+        .arg(root)
+        .arg("--output-dir")
+        .arg(out)
+        .args(extra)
+        .assert()
+}
+
+fn stderr(result: &Assert) -> String {
+    String::from_utf8_lossy(&result.get_output().stderr).to_string()
+}
+
 fn compare_files(file1: &str, file2: &str) -> Result<()> {
     let file1_content = fs::read_to_string(file1)?;
     let file2_content = fs::read_to_string(file2)?;
@@ -16,15 +34,15 @@ fn compare_files(file1: &str, file2: &str) -> Result<()> {
 }
 
 fn compare_dirs(ground_truth: &str, output: &str) -> Result<()> {
-    let mut ground_truth_files = fs::read_dir(ground_truth)?;
-    while let Some(file) = ground_truth_files.next() {
+    let ground_truth_files = fs::read_dir(ground_truth)?;
+    for file in ground_truth_files {
         let file = file?;
         let path = file.path();
         let file_to_compare = format!("{}/{}", output, path.file_name().unwrap().to_str().unwrap());
         if path.is_dir() {
-            compare_dirs(&path.to_str().unwrap(), &file_to_compare)?;
+            compare_dirs(path.to_str().unwrap(), &file_to_compare)?;
         } else {
-            compare_files(&path.to_str().unwrap(), &file_to_compare)?;
+            compare_files(path.to_str().unwrap(), &file_to_compare)?;
         }
     }
     Ok(())
@@ -35,21 +53,346 @@ fn test_basic_site_generation() -> Result<()> {
     let temp_dir = TempDir::new()?;
     let output_dir = temp_dir.path().join("_site");
 
-    let mut cmd = Command::cargo_bin("crustdown")?;
+    build(
+        Path::new(INPUT_CONTENT_LOCATION),
+        &output_dir,
+        &["--posts-dir", INPUT_POST_LOCATION],
+    )
+    .success();
 
-    let result = cmd
-        .arg("--root-dir")
-        .arg(INPUT_CONTENT_LOCATION)
-        .arg("--posts-dir")
-        .arg(INPUT_POST_LOCATION)
-        .arg("--output-dir")
-        .arg(&output_dir)
-        .assert()
+    compare_dirs(OUTPUT_POST_LOCATION, output_dir.to_str().unwrap())?;
+
+    Ok(())
+}
+
+#[test]
+fn test_drafts_and_future_posts() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let output_dir = temp_dir.path().join("_site");
+
+    let build_fixtures = |extra: &[&str]| {
+        let args = [&["--posts-dir", INPUT_POST_LOCATION], extra].concat();
+        build(Path::new(INPUT_CONTENT_LOCATION), &output_dir, &args).success();
+    };
+
+    let draft = output_dir.join("posts/unfinished/index.html");
+    let future = output_dir.join("posts/2999-01-01-from-the-future/index.html");
+    let drafts_dir_post = output_dir.join("posts/idea/index.html");
+
+    build_fixtures(&[]);
+    assert!(!draft.exists());
+    assert!(!future.exists());
+    assert!(!drafts_dir_post.exists());
+    assert!(!output_dir.join("tags/drafts/index.html").exists());
+
+    build_fixtures(&["--future"]);
+    assert!(!draft.exists());
+    assert!(future.exists());
+    assert!(!drafts_dir_post.exists());
+
+    build_fixtures(&["--drafts"]);
+    assert!(draft.exists());
+    assert!(future.exists());
+    assert!(drafts_dir_post.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_build_errors_are_reported_together() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Errors\ndescription: A site with broken posts\n",
+    )?;
+    fs::write(
+        posts_dir.join("good.md"),
+        "---\ntitle: Good\ndescription: Builds\ndate: 2024-01-01\ntags: [ok]\n---\nFine",
+    )?;
+    fs::write(
+        posts_dir.join("no-title.md"),
+        "---\ndescription: No title\ndate: 2024-01-01\ntags: [ok]\n---\nBody",
+    )?;
+    fs::write(
+        posts_dir.join("broken.md"),
+        "---\ntitle: Broken\ntags: a: b\n---\nBody",
+    )?;
+    let output_dir = site_dir.path().join("out");
+
+    let stderr = stderr(&build(site_dir.path(), &output_dir, &[]).failure());
+    assert!(stderr.contains("error: _posts/broken.md:3: Invalid front matter"));
+    assert!(stderr.contains("error: _posts/no-title.md: Title is required"));
+    assert!(stderr.contains("Build failed with 2 error(s)"));
+    assert!(!output_dir.exists());
+
+    build(site_dir.path(), &output_dir, &["--keep-going"]).failure();
+    assert!(output_dir.join("posts/good/index.html").exists());
+    assert!(output_dir.join("index.html").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_incremental_rebuild() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Incremental\ndescription: A site built twice\n",
+    )?;
+    let post = |title: &str| {
+        format!("---\ntitle: {title}\ndescription: A post\ndate: 2024-01-01\ntags: [ok]\n---\nBody")
+    };
+    fs::write(posts_dir.join("kept.md"), post("Kept"))?;
+    fs::write(posts_dir.join("deleted.md"), post("Deleted"))?;
+    let output_dir = site_dir.path().join("out");
+
+    build(site_dir.path(), &output_dir, &[]).success();
+    let kept = output_dir.join("posts/kept/index.html");
+    let deleted = output_dir.join("posts/deleted/index.html");
+    assert!(deleted.exists());
+
+    // Unchanged posts are not written again, so the marker survives
+    fs::write(&kept, "marker")?;
+    fs::remove_file(posts_dir.join("deleted.md"))?;
+    build(site_dir.path(), &output_dir, &[]).success();
+    assert_eq!(fs::read_to_string(&kept)?, "marker");
+    assert!(!deleted.exists());
+    assert!(!output_dir.join("posts/deleted").exists());
+    assert!(!fs::read_to_string(output_dir.join("index.html"))?.contains("Deleted"));
+
+    build(site_dir.path(), &output_dir, &["--clean"]).success();
+    assert!(fs::read_to_string(&kept)?.contains("Kept"));
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_going_keeps_failed_posts() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(posts_dir.join("broken"))?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Keep going\ndescription: A post breaks between two builds\n",
+    )?;
+    fs::write(
+        posts_dir.join("ok.md"),
+        "---\ntitle: Ok\ndate: 2024-01-01\ntags: [ok]\n---\nBody",
+    )?;
+    fs::write(
+        posts_dir.join("broken/index.md"),
+        "---\ntitle: Broken\ndate: 2024-01-02\ntags: [ok]\n---\nBody",
+    )?;
+    fs::write(posts_dir.join("broken/photo.jpg"), "photo")?;
+    let output_dir = site_dir.path().join("out");
+
+    build(site_dir.path(), &output_dir, &[]).success();
+    let page = output_dir.join("posts/broken/index.html");
+    let photo = output_dir.join("posts/broken/photo.jpg");
+    let good_page = fs::read_to_string(&page)?;
+
+    // The broken post keeps its last good page, twice in a row
+    fs::write(
+        posts_dir.join("broken/index.md"),
+        "---\ntitle: [Broken\n---\nBody",
+    )?;
+    for _ in 0..2 {
+        let result = build(site_dir.path(), &output_dir, &["--keep-going"]).failure();
+        assert!(stderr(&result).contains("index.md"));
+        assert_eq!(fs::read_to_string(&page)?, good_page);
+        assert!(photo.exists());
+        let cache = fs::read_to_string(output_dir.join(".crustdown-cache.json"))?;
+        assert!(cache.contains("posts/broken/index.html"));
+    }
+
+    // Once the post is gone, so is its page
+    fs::remove_dir_all(posts_dir.join("broken"))?;
+    build(site_dir.path(), &output_dir, &[]).success();
+    assert!(!page.exists());
+    assert!(!photo.exists());
+    assert!(output_dir.join("posts/ok/index.html").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_build_is_deterministic() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+
+    for jobs in ["1", "8"] {
+        let output_dir = temp_dir.path().join(jobs);
+        build(
+            Path::new(INPUT_CONTENT_LOCATION),
+            &output_dir,
+            &["--posts-dir", INPUT_POST_LOCATION, "--jobs", jobs],
+        )
         .success();
+        compare_dirs(OUTPUT_POST_LOCATION, output_dir.to_str().unwrap())?;
+    }
+    compare_files(
+        temp_dir
+            .path()
+            .join("1/.crustdown-cache.json")
+            .to_str()
+            .unwrap(),
+        temp_dir
+            .path()
+            .join("8/.crustdown-cache.json")
+            .to_str()
+            .unwrap(),
+    )?;
 
-    result.success();
+    Ok(())
+}
 
-    compare_dirs(OUTPUT_POST_LOCATION, output_dir.to_str().unwrap())?;
+#[test]
+fn test_paginated_listings() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(&posts_dir)?;
+    let site_config = site_dir.path().join("_site.yml");
+    fs::write(
+        &site_config,
+        "title: Paginated\ndescription: Many posts\npaginate: 2\n",
+    )?;
+    for day in 1..=5 {
+        fs::write(
+            posts_dir.join(format!("post-{day}.md")),
+            format!("---\ntitle: Post {day}\ndate: 2024-01-0{day}\ntags: [all]\n---\nBody"),
+        )?;
+    }
+    let output_dir = site_dir.path().join("out");
+
+    build(site_dir.path(), &output_dir, &[]).success();
+    let index = fs::read_to_string(output_dir.join("index.html"))?;
+    assert!(index.contains("Post 5") && index.contains("Post 4"));
+    assert!(!index.contains("Post 3"));
+    assert!(index.contains("Page 1 of 3"));
+    assert!(index.contains("rel=\"next\""));
+
+    let page_3 = fs::read_to_string(output_dir.join("page/3/index.html"))?;
+    assert!(page_3.contains("Post 1") && !page_3.contains("Post 2"));
+    assert!(page_3.contains("rel=\"prev\"") && !page_3.contains("rel=\"next\""));
+    assert!(output_dir.join("tags/all/page/3/index.html").exists());
+
+    // Without pagination the extra pages are gone
+    fs::write(&site_config, "title: Paginated\ndescription: Many posts\n")?;
+    build(site_dir.path(), &output_dir, &[]).success();
+    let index = fs::read_to_string(output_dir.join("index.html"))?;
+    assert!(index.contains("Post 1") && !index.contains("Page 1"));
+    assert!(!output_dir.join("page").exists());
+    assert!(!output_dir.join("tags/all/page").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_clashing_routes() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Clashes\ndescription: Posts sharing their route\n",
+    )?;
+    for name in ["first", "second"] {
+        fs::write(
+            posts_dir.join(format!("{name}.md")),
+            format!(
+                "---\ntitle: {name}\ndate: 2024-01-01\ntags: [ok]\npermalink: /same/\n---\nBody"
+            ),
+        )?;
+    }
+    fs::write(
+        posts_dir.join("home.md"),
+        "---\ntitle: Home\ndate: 2024-01-01\ntags: [ok]\npermalink: /index.html\n---\nBody",
+    )?;
+    let output_dir = site_dir.path().join("out");
+
+    let stderr = stderr(&build(site_dir.path(), &output_dir, &[]).failure());
+    assert!(stderr.contains(
+        "error: _posts/second.md: same/index.html is already written by _posts/first.md"
+    ));
+    assert!(
+        stderr.contains(
+            "error: page 1 of the index: index.html is already written by _posts/home.md"
+        )
+    );
+    assert!(stderr.contains("Build failed with 2 error(s)"));
+    assert!(!output_dir.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_drafts_dir_clashes_and_assets() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    let drafts_dir = site_dir.path().join("_drafts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::create_dir_all(drafts_dir.join("trip"))?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Drafts\ndescription: A site with drafts\n",
+    )?;
+    let post = "---\ntitle: Hello\ndate: 2024-01-01\ntags: [ok]\n---\nBody";
+    fs::write(posts_dir.join("hello.md"), post)?;
+    fs::write(drafts_dir.join("hello.md"), post)?;
+    fs::write(drafts_dir.join("trip/index.md"), post)?;
+    fs::write(drafts_dir.join("trip/photo.jpg"), "photo")?;
+    let output_dir = site_dir.path().join("out");
+
+    let errors = stderr(&build(site_dir.path(), &output_dir, &["--drafts"]).failure());
+    assert!(errors.contains("error: _drafts/hello.md: hello.md is in the posts directory too"));
+    assert!(errors.contains("Build failed with 1 error(s)"));
+
+    // Errors name the directory of the post
+    fs::write(
+        drafts_dir.join("untitled.md"),
+        "---\ndate: 2024-01-01\n---\nBody",
+    )?;
+    let errors = stderr(&build(site_dir.path(), &output_dir, &["--drafts"]).failure());
+    assert!(errors.contains("error: _drafts/untitled.md: Title is required"));
+    fs::remove_file(drafts_dir.join("untitled.md"))?;
+
+    fs::remove_file(drafts_dir.join("hello.md"))?;
+    build(site_dir.path(), &output_dir, &[]).success();
+    assert!(!output_dir.join("posts/trip").exists());
+
+    build(site_dir.path(), &output_dir, &["--drafts"]).success();
+    assert!(output_dir.join("posts/trip/index.html").exists());
+    assert_eq!(
+        fs::read_to_string(output_dir.join("posts/trip/photo.jpg"))?,
+        "photo"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_liquid_warning_lines() -> Result<()> {
+    let site_dir = TempDir::new()?;
+    let posts_dir = site_dir.path().join("_posts");
+    fs::create_dir_all(&posts_dir)?;
+    fs::write(
+        site_dir.path().join("_site.yml"),
+        "title: Liquid\ndescription: Imported posts\nliquid: true\n",
+    )?;
+    fs::write(
+        posts_dir.join("a.md"),
+        "---\ntitle: A\ndate: 2024-01-01\ntags: [ok]\nlayout: post\n---\nIntro\n{% post_url foo %}\n",
+    )?;
+
+    let stderr = stderr(&build(site_dir.path(), &site_dir.path().join("out"), &[]).success());
+    assert!(
+        stderr.contains("warning: _posts/a.md:8: Unsupported Liquid tag post_url"),
+        "{}",
+        stderr
+    );
 
     Ok(())
 }